@@ -1,5 +1,5 @@
 _aletheia() {
-  local commands="backup restore update update_gamedb update_custom_gamedbs"
+  local commands="backup restore undo_restore snapshots prune pin unpin rotate_key update update_gamedb update_custom_gamedbs"
   local input="${COMP_WORDS[COMP_CWORD]}"
 
   if [[ ${COMP_CWORD} -eq 1 ]]; then
@@ -1,7 +1,13 @@
-set -l commands backup restore update update_gamedb update_custom_gamedbs
+set -l commands backup restore undo_restore snapshots prune pin unpin rotate_key update update_gamedb update_custom_gamedbs
 
 complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "backup" -d "Create a backup"
 complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "restore" -d "Restore from backup"
+complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "undo_restore" -d "Undo the last restore"
+complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "snapshots" -d "List backup snapshots"
+complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "prune" -d "Remove snapshots outside the retention policy"
+complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "pin" -d "Protect a snapshot from pruning"
+complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "unpin" -d "Allow a snapshot to be pruned"
+complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "rotate_key" -d "Re-encrypt backups with a new key"
 complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "update" -d "Update the application"
 complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "update_gamedb" -d "Update GameDB"
 complete -c aletheia -n "not __fish_seen_subcommand_from $commands" -a "update_custom_gamedbs" -d "Update custom GameDBs"
@@ -1,6 +1,16 @@
 // SPDX-FileCopyrightText: 2025 Spencer
 // SPDX-License-Identifier: AGPL-3.0-only
 
+mod conflict;
+mod encryption;
+mod install;
+mod local_changes;
+mod plan;
 mod steam;
 
+pub use conflict::settle_conflict;
+pub use encryption::unlock_backups;
+pub use install::choose_install;
+pub use local_changes::confirm_local_changes;
+pub use plan::{PlanFormat, print_plans};
 pub use steam::ensure_steam_account_selected;
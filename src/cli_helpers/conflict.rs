@@ -0,0 +1,57 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::config::Config;
+use crate::gamedb::GameDbEntry;
+use crate::operations::{Conflict, Key, Resolution, resolve_conflict};
+use crate::scanner::Game;
+use std::io::{self, IsTerminal, Write};
+
+/// Settles a conflict with `choice`, asking how to when it isn't given.
+pub fn settle_conflict(
+    conflict: &Conflict, game: &Game, entry: &GameDbEntry, config: &Config, key: Option<&Key>, choice: Option<Resolution>
+) {
+    let Some(resolution) = choice.or_else(|| ask_resolution(conflict)) else {
+        eprintln!("{conflict}. Run again with --conflict keep-local, keep-remote or keep-both.");
+        return;
+    };
+
+    if let Err(e) = resolve_conflict(conflict, game, entry, config, key, resolution) {
+        eprintln!("Failed to resolve {}'s conflict: {e}", game.name);
+    } else {
+        println!("Resolved {}'s conflict.", game.name);
+    }
+}
+
+fn ask_resolution(conflict: &Conflict) -> Option<Resolution> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+
+    println!("{conflict}.");
+    println!("1. Keep the saves on this device");
+    println!("2. Keep the saves from {}", conflict.hostname.as_deref().unwrap_or("the other device"));
+    println!("3. Keep both, setting the saves on this device aside as a conflict copy");
+
+    loop {
+        print!("Enter your choice (1-3): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => break None,
+            Ok(_) => {}
+            Err(_) => {
+                eprintln!("Error reading input. Please try again.");
+                continue;
+            }
+        }
+
+        match input.trim() {
+            "1" => break Some(Resolution::Local),
+            "2" => break Some(Resolution::Remote),
+            "3" => break Some(Resolution::Both),
+            _ => eprintln!("Invalid choice. Please enter a number between 1 and 3.")
+        }
+    }
+}
@@ -0,0 +1,20 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::config::{Config, KeySource};
+use crate::operations::{EncryptionError, Key, PASSPHRASE_VAR, load_key};
+use std::io::IsTerminal;
+
+/// Loads the key backups are encrypted with, asking for the passphrase when it isn't set through [`PASSPHRASE_VAR`].
+pub fn unlock_backups(config: &Config) -> Result<Option<Key>, EncryptionError> {
+    let passphrase = if config.encryption == Some(KeySource::Passphrase)
+        && std::env::var(PASSPHRASE_VAR).is_err()
+        && std::io::stdin().is_terminal()
+    {
+        rpassword::prompt_password("Backup passphrase: ").ok()
+    } else {
+        None
+    };
+
+    load_key(config, passphrase)
+}
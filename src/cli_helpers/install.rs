@@ -0,0 +1,36 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::io::{self, IsTerminal, Write};
+
+/// Asks which launcher's install of a game to restore to when stdin is a terminal.
+pub fn choose_install(game: &str, sources: &[String]) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        eprintln!("{game} is installed from {}. Run again with --source to choose one.", sources.join(", "));
+        return None;
+    }
+
+    println!("{game} is installed from multiple launchers. Please choose one to restore to:");
+
+    for (i, source) in sources.iter().enumerate() {
+        println!("{}. {source}", i + 1);
+    }
+
+    let count = sources.len();
+
+    loop {
+        print!("Enter your choice (1-{count}): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        // Nothing more can be read once stdin is closed
+        if matches!(io::stdin().read_line(&mut input), Ok(0) | Err(_)) {
+            return None;
+        }
+
+        match input.trim().parse::<usize>() {
+            Ok(num) if num >= 1 && num <= count => break Some(sources[num - 1].clone()),
+            _ => eprintln!("Invalid choice. Please enter a number between 1 and {count}.")
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::operations::LocalChanges;
+use std::io::{self, IsTerminal, Write};
+
+/// Lists the saves a restore would replace, returning whether to replace them anyway when stdin is a terminal.
+pub fn confirm_local_changes(changes: &LocalChanges) -> bool {
+    println!("{changes}:");
+
+    for file in &changes.files {
+        let reason = if file.newer {
+            "newer"
+        } else {
+            "not backed up"
+        };
+        println!("  {} ({reason})", file.path.display());
+    }
+
+    if !io::stdin().is_terminal() {
+        eprintln!("Skipped restoring {}. Run again with --force to replace them.", changes.game);
+        return false;
+    }
+
+    print!("Replace them? [y/N]: ");
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+
+    if io::stdin().read_line(&mut input).is_err() {
+        return false;
+    }
+
+    matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+}
@@ -0,0 +1,28 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::operations::Plan;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanFormat {
+    Text,
+    Json
+}
+
+impl PlanFormat {
+    pub fn parse(input: Option<&String>) -> Option<Self> {
+        match input.map(String::as_str) {
+            None | Some("text") => Some(Self::Text),
+            Some("json") => Some(Self::Json),
+            _ => None
+        }
+    }
+}
+
+/// Prints what a dry run would do. JSON is printed as a single array so it can be parsed as a whole.
+pub fn print_plans(plans: &[Plan], format: PlanFormat) {
+    match format {
+        PlanFormat::Text => plans.iter().for_each(|plan| print!("{plan}")),
+        PlanFormat::Json => println!("{}", serde_json::to_string_pretty(plans).unwrap())
+    }
+}
@@ -5,7 +5,12 @@
 mod update;
 
 mod backup;
+mod pin;
+mod prune;
 mod restore;
+mod rotate_key;
+mod snapshots;
+mod undo_restore;
 mod update_custom;
 mod update_gamedb;
 
@@ -13,10 +18,18 @@ mod update_gamedb;
 pub use update::Update;
 
 pub use backup::Backup;
+pub use pin::{Pin, Unpin};
+pub use prune::Prune;
 pub use restore::Restore;
+pub use rotate_key::RotateKey;
+pub use snapshots::Snapshots;
+pub use undo_restore::UndoRestore;
 pub use update_custom::UpdateCustom;
 pub use update_gamedb::UpdateGameDb;
 
+/// Flags that never take a value, so what follows them is parsed on its own, like the game in `restore --force Game`.
+const SWITCHES: [&str; 4] = ["disable", "dry-run", "force", "remove-extra"];
+
 pub struct Args {
     pub positional: Vec<String>,
     pub flags: Vec<Flag>
@@ -38,13 +51,17 @@ impl Flag {
 }
 
 impl Args {
-    pub fn parse(mut args: impl Iterator<Item = String>) -> Self {
+    pub fn parse(args: impl Iterator<Item = String>) -> Self {
+        let mut args = args.peekable();
         let mut positional = Vec::new();
         let mut flags = Vec::new();
 
         while let Some(arg) = args.next() {
             if let Some(name) = arg.strip_prefix("--") {
-                if let Some(value) = args.next().filter(|next| !next.starts_with('-')) {
+                // A flag directly after another one is left to be parsed on its own
+                if !SWITCHES.contains(&name)
+                    && let Some(value) = args.next_if(|next| !next.starts_with('-'))
+                {
                     flags.push(Flag::with_value(name, value));
                 } else {
                     flags.push(Flag::new(name));
@@ -57,7 +74,6 @@ impl Args {
         Self { positional, flags }
     }
 
-    #[expect(unused, reason = "Will be used in the future")]
     pub fn has_flag(&self, name: &str) -> bool {
         self.flags.iter().any(|f| f.name == name)
     }
@@ -69,8 +85,29 @@ impl Args {
     pub fn get_flag_value(&self, name: &str) -> Option<&String> {
         self.get_flag(name)?.value.as_ref()
     }
+
+    /// Values of a flag that can be given more than once.
+    pub fn get_flag_values(&self, name: &str) -> impl Iterator<Item = &String> {
+        self.flags.iter().filter(move |f| f.name == name).filter_map(|f| f.value.as_ref())
+    }
 }
 
 pub trait Command {
     fn run(args: Args, config: &crate::config::Config);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_switches() {
+        // As run by `aletheia restore --force "Unit Test" --source Heroic`
+        let args = Args::parse(["--force", "Unit Test", "--source", "Heroic"].map(String::from).into_iter());
+
+        assert_eq!(args.positional, ["Unit Test"]);
+        assert!(args.has_flag("force"));
+        assert_eq!(args.get_flag("force").unwrap().value, None);
+        assert_eq!(args.get_flag_value("source").map(String::as_str), Some("Heroic"));
+    }
+}
@@ -2,11 +2,12 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 use super::{Args, Command};
-use crate::cli_helpers::ensure_steam_account_selected;
+use crate::cli_helpers::{PlanFormat, ensure_steam_account_selected, print_plans, settle_conflict, unlock_backups};
 use crate::config::Config;
-use crate::gamedb;
+use crate::gamedb::{self, GameDbEntry};
 use crate::infer;
-use crate::operations::backup_game;
+use crate::operations::{BackupError, BackupOptions, Key, Plan, Resolution, backup_game, pull_storage};
+use crate::scanner::Game;
 
 pub struct Backup;
 
@@ -24,22 +25,84 @@ impl Command for Backup {
             return;
         }
 
-        if args.positional.is_empty() {
-            for game in &installed_games {
-                if let Err(e) = backup_game(game, config, &game_db[&game.name]) {
-                    eprintln!("Failed to backup {}: {}", game.name, e);
-                } else {
-                    println!("Backed up {}.", game.name);
-                }
+        let choice = match args.get_flag_value("conflict") {
+            Some(value) => {
+                let Some(resolution) = Resolution::parse(value) else {
+                    eprintln!("Invalid conflict resolution {value}, expected keep-local, keep-remote or keep-both.");
+                    return;
+                };
+
+                Some(resolution)
+            }
+            None => None
+        };
+
+        let dry_run = args.has_flag("dry-run");
+        let Some(format) = PlanFormat::parse(args.get_flag_value("format")) else {
+            eprintln!("Invalid format, expected text or json.");
+            return;
+        };
+
+        let mut mirror = match pull_storage(config) {
+            Ok(mirror) => mirror,
+            Err(e) => {
+                eprintln!("Failed to download backups: {e}");
+                return;
+            }
+        };
+
+        let key = match unlock_backups(config) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        let mut plans = vec![];
+
+        for game in installed_games.iter().filter(|game| args.positional.is_empty() || args.positional.contains(&game.name)) {
+            let entry = &game_db[&game.name];
+
+            if dry_run {
+                plans.extend(plan(game, entry, config, key.as_ref(), choice));
+            } else {
+                backup(game, entry, config, key.as_ref(), choice);
             }
-        } else {
-            installed_games.iter().filter(|game| args.positional.contains(&game.name)).for_each(|game| {
-                if let Err(e) = backup_game(game, config, &game_db[&game.name]) {
-                    eprintln!("Failed to backup {}: {}", game.name, e);
-                } else {
-                    println!("Backed up {}.", game.name);
-                }
-            });
+        }
+
+        if dry_run {
+            print_plans(&plans, format);
+            return;
+        }
+
+        if let Some(mirror) = &mut mirror
+            && let Err(e) = mirror.push()
+        {
+            eprintln!("Failed to upload backups: {e}");
+        }
+    }
+}
+
+fn backup(game: &Game, entry: &GameDbEntry, config: &Config, key: Option<&Key>, choice: Option<Resolution>) {
+    match backup_game(game, config, entry, key, BackupOptions::default()) {
+        Ok(plan) if plan.changed() => println!("Backed up {}.", game.name),
+        Ok(_) => println!("{} is already backed up.", game.name),
+        Err(BackupError::Conflict(conflict)) => settle_conflict(&conflict, game, entry, config, key, choice),
+        Err(e) => eprintln!("Failed to backup {}: {}", game.name, e)
+    }
+}
+
+fn plan(game: &Game, entry: &GameDbEntry, config: &Config, key: Option<&Key>, choice: Option<Resolution>) -> Option<Plan> {
+    match backup_game(game, config, entry, key, BackupOptions { resolution: choice, dry_run: true }) {
+        Ok(plan) => Some(plan),
+        Err(BackupError::Conflict(conflict)) => {
+            eprintln!("{conflict}. Run again with --conflict keep-local, keep-remote or keep-both.");
+            None
+        }
+        Err(e) => {
+            eprintln!("Failed to plan backing up {}: {e}", game.name);
+            None
         }
     }
 }
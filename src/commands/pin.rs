@@ -0,0 +1,112 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Args, Command};
+use crate::cli_helpers::unlock_backups;
+use crate::config::Config;
+use crate::gamedb::GameInfo;
+use crate::operations::{SnapshotSelector, list_snapshots, pull_storage, select_snapshot, write_manifest};
+use crate::utils;
+
+pub struct Pin;
+pub struct Unpin;
+
+impl Command for Pin {
+    fn run(args: Args, config: &Config) {
+        let tag = args.get_flag_value("tag").cloned();
+        set_pinned(&args, config, true, tag);
+    }
+}
+
+impl Command for Unpin {
+    fn run(args: Args, config: &Config) {
+        set_pinned(&args, config, false, None);
+    }
+}
+
+fn set_pinned(args: &Args, config: &Config, pinned: bool, tag: Option<String>) {
+    let [game_name, snapshot_id] = args.positional.as_slice() else {
+        eprintln!("Expected a game name and a snapshot.");
+        return;
+    };
+
+    let mut mirror = match pull_storage(config) {
+        Ok(mirror) => mirror,
+        Err(e) => {
+            eprintln!("Failed to download backups: {e}");
+            return;
+        }
+    };
+
+    let key = match unlock_backups(config) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Failed to unlock backups: {e}");
+            return;
+        }
+    };
+
+    let game_dir = config.save_dir.join(utils::sanitize_game_name(game_name).as_ref());
+    let snapshots = match list_snapshots(&game_dir, key.as_ref()) {
+        Ok(snapshots) => snapshots,
+        Err(e) => {
+            eprintln!("Failed to read {game_name}'s snapshots: {e}");
+            return;
+        }
+    };
+
+    let Some(mut snapshot) = select_snapshot(snapshots, &SnapshotSelector::parse(snapshot_id)) else {
+        eprintln!("No matching snapshot found for {game_name}.");
+        return;
+    };
+
+    set_protection(&mut snapshot.manifest, pinned, tag);
+
+    // Encrypting the manifest of a plain snapshot would make it pass authentication
+    if let Err(e) = write_manifest(&snapshot.path, &snapshot.manifest, key.as_ref().filter(|_| snapshot.encrypted)) {
+        eprintln!("Failed to update snapshot {}: {e}", snapshot.id);
+        return;
+    }
+
+    if pinned {
+        println!("Pinned {game_name} snapshot {}.", snapshot.id);
+    } else {
+        println!("Unpinned {game_name} snapshot {}.", snapshot.id);
+    }
+
+    if let Some(mirror) = &mut mirror
+        && let Err(e) = mirror.push()
+    {
+        eprintln!("Failed to upload backups: {e}");
+    }
+}
+
+/// Tags are only replaced when a new one is given, as they keep a snapshot from being pruned whether it's pinned or not.
+fn set_protection(manifest: &mut GameInfo, pinned: bool, tag: Option<String>) {
+    manifest.pinned = pinned;
+
+    if tag.is_some() {
+        manifest.tag = tag;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pinning_keeps_tag() {
+        let mut manifest: GameInfo = serde_yaml::from_str("name: Unit Test\ntag: release\nfiles: []").unwrap();
+
+        set_protection(&mut manifest, true, None);
+        assert!(manifest.pinned);
+        assert_eq!(manifest.tag.as_deref(), Some("release"));
+
+        set_protection(&mut manifest, false, None);
+        assert!(!manifest.pinned);
+        assert_eq!(manifest.tag.as_deref(), Some("release"));
+
+        set_protection(&mut manifest, true, Some("1.0".into()));
+        assert_eq!(manifest.tag.as_deref(), Some("1.0"));
+    }
+}
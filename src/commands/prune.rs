@@ -0,0 +1,96 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Args, Command};
+use crate::cli_helpers::unlock_backups;
+use crate::config::Config;
+use crate::operations::{collect_garbage, latest_snapshot, prune_game, pull_storage};
+use crate::utils;
+use std::collections::HashSet;
+
+pub struct Prune;
+
+impl Command for Prune {
+    fn run(args: Args, config: &Config) {
+        let mut mirror = match pull_storage(config) {
+            Ok(mirror) => mirror,
+            Err(e) => {
+                eprintln!("Failed to download backups: {e}");
+                return;
+            }
+        };
+
+        if !config.save_dir.exists() {
+            eprintln!("Backup directory doesn't exist.");
+            return;
+        }
+
+        let key = match unlock_backups(config) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        let dry_run = args.has_flag("dry-run");
+        let verb = if dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+        let mut pruned_snapshots = HashSet::new();
+        let mut freed = 0;
+
+        for game in std::fs::read_dir(&config.save_dir).unwrap() {
+            let game_dir = game.unwrap().path();
+            let game_name = game_dir.file_name().unwrap().to_string_lossy();
+
+            if !game_dir.is_dir() || game_name.starts_with('.') {
+                continue;
+            }
+
+            let Ok(Some(latest)) = latest_snapshot(&game_dir, key.as_ref()) else {
+                continue;
+            };
+
+            let name = latest.manifest.name;
+
+            if !args.positional.is_empty() && !args.positional.contains(&name) {
+                continue;
+            }
+
+            match prune_game(&game_dir, key.as_ref(), config.retention_for(&name), dry_run) {
+                Ok(pruned) => {
+                    for snapshot in pruned {
+                        println!("{verb} {name} snapshot {} ({}).", snapshot.id, utils::format_size(snapshot.size));
+                        freed += snapshot.size;
+                        pruned_snapshots.insert(snapshot.path);
+                    }
+                }
+                Err(e) => eprintln!("Failed to prune {name}: {e}")
+            }
+        }
+
+        match collect_garbage(&config.save_dir, key.as_ref(), &pruned_snapshots, dry_run) {
+            Ok((0, _)) => {}
+            Ok((blobs, size)) => {
+                println!("{verb} {blobs} unreferenced blobs ({}).", utils::format_size(size));
+                freed += size;
+            }
+            Err(e) => eprintln!("Failed to remove unreferenced blobs: {e}")
+        }
+
+        if dry_run {
+            println!("Pruning would free {}.", utils::format_size(freed));
+        } else {
+            println!("Freed {}.", utils::format_size(freed));
+
+            if let Some(mirror) = &mut mirror
+                && let Err(e) = mirror.push()
+            {
+                eprintln!("Failed to upload backups: {e}");
+            }
+        }
+    }
+}
@@ -2,22 +2,24 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 use super::{Args, Command};
-use crate::cli_helpers::ensure_steam_account_selected;
+use crate::cli_helpers::{
+    PlanFormat, choose_install, confirm_local_changes, ensure_steam_account_selected, print_plans, settle_conflict, unlock_backups
+};
 use crate::config::Config;
 use crate::gamedb;
 use crate::infer;
-use crate::operations::restore_game;
-use std::fs::File;
+use crate::operations::{
+    Key, Plan, Reason, Resolution, RestoreError, RestoreOptions, Snapshot, SnapshotSelector, list_snapshots, parse_time, pull_storage,
+    restore_game, select_snapshot
+};
+use crate::scanner::Game;
+use glob::Pattern;
+use std::path::{Path, PathBuf};
 
 pub struct Restore;
 
 impl Command for Restore {
     fn run(args: Args, config: &Config) {
-        if !config.save_dir.exists() {
-            eprintln!("Backup directory doesn't exist.");
-            return;
-        }
-
         let installed_games = gamedb::get_installed_games();
 
         if config.steam_account_id.is_none() && installed_games.iter().any(|g| g.source == "Steam") {
@@ -29,6 +31,69 @@ impl Command for Restore {
             return;
         }
 
+        let choice = match args.get_flag_value("conflict") {
+            Some(value) => {
+                let Some(resolution) = Resolution::parse(value) else {
+                    eprintln!("Invalid conflict resolution {value}, expected keep-local, keep-remote or keep-both.");
+                    return;
+                };
+
+                Some(resolution)
+            }
+            None => None
+        };
+
+        let dry_run = args.has_flag("dry-run");
+        let Some(format) = PlanFormat::parse(args.get_flag_value("format")) else {
+            eprintln!("Invalid format, expected text or json.");
+            return;
+        };
+
+        // A dry run can't settle conflicts, so it plans with the given resolution instead
+        let resolution = choice.filter(|_| dry_run);
+        let Some(files) = parse_files(&args) else {
+            return;
+        };
+        let Some(target) = Target::parse(&args) else {
+            return;
+        };
+        let options = RestoreOptions {
+            resolution,
+            force: args.has_flag("force"),
+            dry_run,
+            files: &files,
+            remove_extra: args.has_flag("remove-extra"),
+            ..target.options()
+        };
+
+        let mut mirror = match pull_storage(config) {
+            Ok(mirror) => mirror,
+            Err(e) => {
+                eprintln!("Failed to download backups: {e}");
+                return;
+            }
+        };
+
+        if !config.save_dir.exists() {
+            eprintln!("Backup directory doesn't exist.");
+            return;
+        }
+
+        let key = match unlock_backups(config) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        let Some(selector) = parse_selector(&args) else {
+            return;
+        };
+
+        let game_db = gamedb::parse();
+        let mut plans = vec![];
+
         for game in std::fs::read_dir(&config.save_dir).unwrap() {
             let game_dir = game.unwrap().path();
             let is_dir = game_dir.is_dir();
@@ -38,27 +103,211 @@ impl Command for Restore {
                 continue;
             }
 
-            let manifest_path = game_dir.join("aletheia_manifest.yaml");
-
-            if !manifest_path.exists() {
-                eprintln!("{game_name} is missing a manifest file.");
-                continue;
-            }
-
-            let Ok(manifest) = serde_yaml::from_reader::<File, gamedb::GameInfo>(File::open(manifest_path).unwrap()) else {
-                eprintln!("Failed to parse {}'s manifest.", game_dir.file_name().unwrap().display());
+            let Some((name, snapshot)) = find_snapshot(&game_dir, &game_name, &args, key.as_ref(), &selector) else {
                 continue;
             };
 
-            if !args.positional.is_empty() && !args.positional.contains(&manifest.name) {
-                continue;
-            }
+            // Games are kept apart when restoring several of them into one directory
+            let to_dir = target.to_dir.as_ref().map(|dir| dir.join(game_name.as_ref()));
+            let options = RestoreOptions { to_dir: to_dir.as_deref(), entry: game_db.get(&name), ..options };
 
-            if let Err(e) = restore_game(&game_dir, &manifest, &installed_games, config) {
-                println!("Failed to restore {}: {e}", manifest.name);
+            if dry_run {
+                plans.extend(plan(&snapshot, &installed_games, config, key.as_ref(), options));
             } else {
-                println!("Restored {}.", manifest.name);
+                restore(&snapshot, &installed_games, config, key.as_ref(), options, choice);
+            }
+        }
+
+        if dry_run {
+            print_plans(&plans, format);
+            return;
+        }
+
+        // Settling conflicts can back up saves, and restoring records what this device synced with
+        if let Some(mirror) = &mut mirror
+            && let Err(e) = mirror.push()
+        {
+            eprintln!("Failed to upload backups: {e}");
+        }
+    }
+}
+
+/// The game's name and the snapshot to restore it from, or `None` when it's not restored.
+fn find_snapshot(
+    game_dir: &Path, game_name: &str, args: &Args, key: Option<&Key>, selector: &SnapshotSelector
+) -> Option<(String, Snapshot)> {
+    let snapshots = match list_snapshots(game_dir, key) {
+        Ok(snapshots) => snapshots,
+        Err(e) => {
+            eprintln!("Failed to read {game_name}'s snapshots: {e}");
+            return None;
+        }
+    };
+
+    let Some(name) = snapshots.last().map(|s| s.manifest.name.clone()) else {
+        eprintln!("{game_name} doesn't have any snapshots.");
+        return None;
+    };
+
+    if !args.positional.is_empty() && !args.positional.contains(&name) {
+        return None;
+    }
+
+    let Some(snapshot) = select_snapshot(snapshots, selector) else {
+        eprintln!("No matching snapshot found for {name}.");
+        return None;
+    };
+
+    Some((name, snapshot))
+}
+
+fn restore(
+    snapshot: &Snapshot, installed_games: &[Game], config: &Config, key: Option<&Key>, options: RestoreOptions,
+    choice: Option<Resolution>
+) {
+    let manifest = &snapshot.manifest;
+    let mut options = options;
+    let mut result = restore_game(snapshot, installed_games, config, key, options);
+    let source;
+
+    if let Err(RestoreError::MultipleInstalls(game, sources)) = &result {
+        let Some(chosen) = choose_install(game, sources) else {
+            return;
+        };
+
+        source = chosen;
+        options = RestoreOptions { source: Some(&source), ..options };
+        result = restore_game(snapshot, installed_games, config, key, options);
+    }
+
+    if let Err(RestoreError::LocalChanges(changes)) = &result {
+        if !confirm_local_changes(changes) {
+            return;
+        }
+
+        result = restore_game(snapshot, installed_games, config, key, RestoreOptions { force: true, ..options });
+    }
+
+    match result {
+        Ok(plan) => {
+            println!("Restored {} from snapshot {}.", manifest.name, snapshot.id);
+
+            for file in plan.files.iter().filter(|file| file.reason == Reason::Unmapped) {
+                eprintln!("Skipped {}, it has no equivalent location on this platform.", file.path.display());
             }
         }
+        Err(RestoreError::Conflict(conflict)) => {
+            let game = installed_games
+                .iter()
+                .find(|g| g.name == manifest.name && options.source.is_none_or(|source| g.source.eq_ignore_ascii_case(source)))
+                .unwrap();
+
+            // Installed games are always in the GameDB, and the entry is needed to back up local saves when settling a conflict
+            settle_conflict(&conflict, game, options.entry.unwrap(), config, key, choice);
+        }
+        Err(e) => eprintln!("Failed to restore {}: {e}", manifest.name)
+    }
+}
+
+fn plan(snapshot: &Snapshot, installed_games: &[Game], config: &Config, key: Option<&Key>, options: RestoreOptions) -> Option<Plan> {
+    match restore_game(snapshot, installed_games, config, key, options) {
+        Ok(plan) => Some(plan),
+        Err(RestoreError::Conflict(conflict)) => {
+            eprintln!("{conflict}. Run again with --conflict keep-local, keep-remote or keep-both.");
+            None
+        }
+        Err(RestoreError::LocalChanges(changes)) => {
+            eprintln!("{changes}. Run again with --force to replace them.");
+            None
+        }
+        Err(e @ RestoreError::MultipleInstalls(..)) => {
+            eprintln!("{e}. Run again with --source.");
+            None
+        }
+        Err(e) => {
+            eprintln!("Failed to plan restoring {}: {e}", snapshot.manifest.name);
+            None
+        }
+    }
+}
+
+fn parse_selector(args: &Args) -> Option<SnapshotSelector> {
+    if let Some(snapshot) = args.get_flag_value("snapshot") {
+        Some(SnapshotSelector::parse(snapshot))
+    } else if let Some(date) = args.get_flag_value("as-of") {
+        let Some(time) = parse_time(date) else {
+            eprintln!("Invalid date {date}, expected YYYY-MM-DD or YYYY-MM-DD HH:MM[:SS].");
+            return None;
+        };
+
+        Some(SnapshotSelector::AsOf(time))
+    } else {
+        Some(SnapshotSelector::Latest)
+    }
+}
+
+/// Which install of the game to restore to, or where to restore to instead of where the game is detected.
+struct Target {
+    source: Option<String>,
+    to_dir: Option<PathBuf>,
+    game_root: Option<PathBuf>,
+    #[cfg(unix)]
+    prefix: Option<PathBuf>
+}
+
+impl Target {
+    fn parse(args: &Args) -> Option<Self> {
+        let path = |name: &str| args.get_flag_value(name).map(|value| std::path::absolute(value).unwrap());
+        let target = Self {
+            source: args.get_flag_value("source").cloned(),
+            to_dir: path("to-dir"),
+            game_root: path("game-root"),
+            #[cfg(unix)]
+            prefix: path("prefix")
+        };
+
+        #[cfg(unix)]
+        let relocated = target.game_root.is_some() || target.prefix.is_some();
+
+        #[cfg(windows)]
+        let relocated = target.game_root.is_some();
+
+        #[cfg(windows)]
+        if args.has_flag("prefix") {
+            eprintln!("--prefix is only supported on Linux and macOS.");
+            return None;
+        }
+
+        if target.to_dir.is_some() && relocated {
+            eprintln!("--to-dir can't be combined with --game-root or --prefix.");
+            return None;
+        }
+
+        if target.game_root.is_some() && args.positional.len() != 1 {
+            eprintln!("--game-root needs exactly one game to restore.");
+            return None;
+        }
+
+        Some(target)
+    }
+
+    fn options(&self) -> RestoreOptions<'_> {
+        RestoreOptions {
+            source: self.source.as_deref(),
+            game_root: self.game_root.as_deref(),
+            #[cfg(unix)]
+            prefix: self.prefix.as_deref(),
+            ..RestoreOptions::default()
+        }
+    }
+}
+
+fn parse_files(args: &Args) -> Option<Vec<Pattern>> {
+    match args.get_flag_values("file").map(|pattern| Pattern::new(pattern)).collect() {
+        Ok(files) => Some(files),
+        Err(e) => {
+            eprintln!("Invalid file pattern: {e}");
+            None
+        }
     }
 }
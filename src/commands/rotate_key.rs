@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Args, Command};
+use crate::cli_helpers::unlock_backups;
+use crate::config::{Config, KeySource};
+use crate::operations::{pull_storage, read_secret, rotate_key};
+use std::io::IsTerminal;
+use std::path::absolute;
+
+const NEW_PASSPHRASE_VAR: &str = "ALETHEIA_NEW_PASSPHRASE";
+
+pub struct RotateKey;
+
+impl Command for RotateKey {
+    fn run(args: Args, config: &Config) {
+        let mut mirror = match pull_storage(config) {
+            Ok(mirror) => mirror,
+            Err(e) => {
+                eprintln!("Failed to download backups: {e}");
+                return;
+            }
+        };
+
+        if !config.save_dir.exists() {
+            eprintln!("Backup directory doesn't exist.");
+            return;
+        }
+
+        let source = if args.has_flag("disable") {
+            if config.encryption.is_none() {
+                eprintln!("Backups aren't encrypted.");
+                return;
+            }
+
+            None
+        } else if let Some(path) = args.get_flag_value("key-file") {
+            Some(KeySource::KeyFile(absolute(path).unwrap()))
+        } else {
+            Some(KeySource::Passphrase)
+        };
+
+        let secret = match &source {
+            Some(KeySource::Passphrase) => {
+                let Some(passphrase) = new_passphrase() else {
+                    eprintln!("No new passphrase provided, set {NEW_PASSPHRASE_VAR} or run this in a terminal.");
+                    return;
+                };
+
+                Some(passphrase.into_bytes())
+            }
+            Some(source) => match read_secret(source, None) {
+                Ok(secret) => Some(secret),
+                Err(e) => {
+                    eprintln!("Failed to read key file: {e}");
+                    return;
+                }
+            },
+            None => None
+        };
+
+        let old = match unlock_backups(config) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        match rotate_key(&config.save_dir, old.as_ref(), secret.as_deref()) {
+            Ok(snapshots) => {
+                Config::save(&Config { encryption: source, ..config.clone() });
+                println!("Rewrote {snapshots} snapshots.");
+
+                if let Some(mirror) = &mut mirror
+                    && let Err(e) = mirror.push()
+                {
+                    eprintln!("Failed to upload backups: {e}");
+                }
+            }
+            Err(e) => eprintln!("Failed to rotate key: {e}\nRun rotate_key again with the same new key to finish it.")
+        }
+    }
+}
+
+fn new_passphrase() -> Option<String> {
+    if let Ok(passphrase) = std::env::var(NEW_PASSPHRASE_VAR) {
+        return Some(passphrase).filter(|passphrase| !passphrase.is_empty());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+
+    loop {
+        let passphrase = rpassword::prompt_password("New passphrase: ").ok()?;
+
+        if passphrase.is_empty() {
+            eprintln!("Passphrase can't be empty.");
+        } else if rpassword::prompt_password("Confirm passphrase: ").ok()? == passphrase {
+            return Some(passphrase);
+        } else {
+            eprintln!("Passphrases don't match.");
+        }
+    }
+}
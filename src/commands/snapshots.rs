@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Args, Command};
+use crate::cli_helpers::unlock_backups;
+use crate::config::Config;
+use crate::dirs::get_size;
+use crate::operations::{list_snapshots, pull_storage};
+use crate::utils;
+use chrono::{DateTime, Local};
+
+pub struct Snapshots;
+
+impl Command for Snapshots {
+    fn run(args: Args, config: &Config) {
+        if let Err(e) = pull_storage(config) {
+            eprintln!("Failed to download backups: {e}");
+            return;
+        }
+
+        if !config.save_dir.exists() {
+            eprintln!("Backup directory doesn't exist.");
+            return;
+        }
+
+        let key = match unlock_backups(config) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        for game in std::fs::read_dir(&config.save_dir).unwrap() {
+            let game_dir = game.unwrap().path();
+            let game_name = game_dir.file_name().unwrap().to_string_lossy();
+
+            if !game_dir.is_dir() || game_name.starts_with('.') {
+                continue;
+            }
+
+            let snapshots = match list_snapshots(&game_dir, key.as_ref()) {
+                Ok(snapshots) => snapshots,
+                Err(e) => {
+                    eprintln!("Failed to read {game_name}'s snapshots: {e}");
+                    continue;
+                }
+            };
+
+            let Some(name) = snapshots.last().map(|s| &s.manifest.name) else {
+                continue;
+            };
+
+            if !args.positional.is_empty() && !args.positional.contains(name) {
+                continue;
+            }
+
+            println!("{name}");
+
+            for snapshot in &snapshots {
+                let label = match (&snapshot.manifest.tag, snapshot.manifest.pinned) {
+                    (Some(tag), _) => format!("  [{tag}]"),
+                    (None, true) => "  [pinned]".to_owned(),
+                    (None, false) => String::new()
+                };
+                let conflict = if snapshot.manifest.conflict {
+                    "  [conflict]"
+                } else {
+                    ""
+                };
+
+                println!(
+                    "  {}  {}  {} files  {}{label}{conflict}",
+                    snapshot.id,
+                    DateTime::<Local>::from(snapshot.created()).format("%Y-%m-%d %H:%M:%S"),
+                    snapshot.manifest.files.len(),
+                    utils::format_size(get_size(&snapshot.path))
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Args, Command};
+use crate::cli_helpers::unlock_backups;
+use crate::config::Config;
+use crate::gamedb;
+use crate::operations::{RestoreError, pull_storage, undo_restore};
+
+pub struct UndoRestore;
+
+impl Command for UndoRestore {
+    fn run(args: Args, config: &Config) {
+        if args.positional.is_empty() {
+            eprintln!("Expected the names of the games to undo the last restore of.");
+            return;
+        }
+
+        let mut mirror = match pull_storage(config) {
+            Ok(mirror) => mirror,
+            Err(e) => {
+                eprintln!("Failed to download backups: {e}");
+                return;
+            }
+        };
+
+        let key = match unlock_backups(config) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        let installed_games = gamedb::get_installed_games();
+        let source = args.get_flag_value("source").map(String::as_str);
+
+        for game_name in &args.positional {
+            match undo_restore(game_name, &installed_games, source, config, key.as_ref()) {
+                Ok(true) => println!("Undid the last restore of {game_name}."),
+                Ok(false) => println!("{game_name} hasn't been restored on this device."),
+                Err(RestoreError::GameNotFound) => eprintln!("{game_name} isn't installed."),
+                Err(e @ RestoreError::MultipleInstalls(..)) => eprintln!("{e}. Run again with --source."),
+                Err(e) => eprintln!("Failed to undo the last restore of {game_name}: {e}")
+            }
+        }
+
+        if let Some(mirror) = &mut mirror
+            && let Err(e) = mirror.push()
+        {
+            eprintln!("Failed to upload backups: {e}");
+        }
+    }
+}
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 use crate::dirs;
-use std::path::PathBuf;
+use crate::gamedb::Compression;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 #[cfg(not(target_os = "macos"))]
 use std::fs::{File, create_dir_all};
@@ -15,11 +17,115 @@ use std::fs::File;
 pub struct Config {
     pub custom_databases: Vec<String>,
     pub save_dir: PathBuf,
+    /// Remote storage is mirrored into `save_dir`, which then acts as a cache.
+    pub storage: StorageBackend,
     pub steam_account_id: Option<String>,
+    /// Identifies this device in the snapshots it makes, so backups from other devices sharing `save_dir` can be told apart.
+    #[serde(default)]
+    pub device_id: String,
+    /// Store file contents once by hash instead of per snapshot.
+    pub deduplicate: bool,
+    /// How newly backed up files are compressed. Files that haven't changed keep their existing compression.
+    pub compression: Compression,
+    pub game_compression: HashMap<String, Compression>,
+    /// Encrypts backed up files and manifests when set. Use `rotate_key` to change it so existing backups are re-encrypted.
+    pub encryption: Option<KeySource>,
+    pub retention: RetentionPolicy,
+    pub game_retention: HashMap<String, RetentionPolicy>,
+    /// Glob patterns for files that are never backed up, which can use the same placeholders as the game database.
+    /// A pattern without a directory, such as `*.log`, matches files anywhere.
+    pub exclude: Vec<String>,
+    pub game_exclude: HashMap<String, Vec<String>>,
     #[cfg(feature = "updater")]
     pub check_for_updates: bool
 }
 
+/// Where backups are kept. Credentials are read from the environment so they're never saved.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// Directly in `save_dir`.
+    #[default]
+    Local,
+    /// Another directory, such as a network share or removable drive.
+    Directory {
+        path: PathBuf
+    },
+    /// Authenticates with `private_key`, `ALETHEIA_SFTP_PASSWORD` or the SSH agent, in that order.
+    Sftp {
+        host: String,
+        #[serde(default = "default_sftp_port")]
+        port: u16,
+        username: String,
+        path: String,
+        private_key: Option<PathBuf>
+    },
+    /// Authenticates with `username` and `ALETHEIA_WEBDAV_PASSWORD` when a username is set.
+    WebDav {
+        url: String,
+        username: Option<String>
+    },
+    /// Authenticates with `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+    /// `path_style` puts the bucket in the path instead of the host name, which most self-hosted servers need.
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+        #[serde(default)]
+        path_style: bool
+    },
+    /// A remote set up with `rclone config`, such as `gdrive:aletheia`. Needs `rclone` to be installed.
+    /// Setting `save_dir` to the remote is the same as setting this.
+    Rclone {
+        remote: String
+    }
+}
+
+const fn default_sftp_port() -> u16 {
+    22
+}
+
+/// Returns `path` if it's an rclone remote, either `name:path` or an on the fly backend such as `:local:path`.
+/// Single letters are drive letters on Windows, so they aren't treated as remotes.
+fn rclone_remote(path: &Path) -> Option<&str> {
+    let path = path.to_str()?;
+    let (name, _) = path.split_once(':')?;
+
+    (name.is_empty() || (name.len() > 1 && !name.contains(['/', '\\']))).then_some(path)
+}
+
+/// Where the key backups are encrypted with comes from. Only the source is saved, never the key itself.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Passphrase,
+    KeyFile(PathBuf)
+}
+
+/// Which snapshots `prune` keeps. A snapshot is kept if any rule matches it, and every snapshot is kept when no rule is set.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Keep the newest N snapshots.
+    pub last: Option<usize>,
+    /// Keep the newest snapshot of each day for the last N days.
+    pub daily: Option<u32>,
+    /// Keep the newest snapshot of each week for the last N weeks.
+    pub weekly: Option<u32>,
+    /// Keep the newest snapshot of each month for the last N months.
+    pub monthly: Option<u32>,
+    /// Keep every snapshot made within the last N days.
+    pub within_days: Option<u32>
+}
+
+impl RetentionPolicy {
+    pub const fn is_empty(&self) -> bool {
+        self.last.is_none() && self.daily.is_none() && self.weekly.is_none() && self.monthly.is_none() && self.within_days.is_none()
+    }
+}
+
 impl Config {
     #[cfg(target_os = "macos")]
     fn get_dir() -> PathBuf {
@@ -51,6 +157,50 @@ impl Config {
         Self::get_dir().join("saves")
     }
 
+    pub fn compression_for(&self, game_name: &str) -> Compression {
+        self.game_compression.get(game_name).copied().unwrap_or(self.compression)
+    }
+
+    /// What's shown as the save directory, which is the remote when it was set as one.
+    pub fn display_save_dir(&self) -> String {
+        match &self.storage {
+            StorageBackend::Rclone { remote } => remote.clone(),
+            _ => self.save_dir.to_string_lossy().into_owned()
+        }
+    }
+
+    /// Sets the save directory from what [`Self::display_save_dir`] showed, keeping it as is when it didn't change.
+    pub fn set_display_save_dir(&mut self, save_dir: &str) {
+        if save_dir == self.display_save_dir() {
+            return;
+        }
+
+        // A new save directory replaces the rclone remote it was set to before
+        if matches!(self.storage, StorageBackend::Rclone { .. }) {
+            self.storage = StorageBackend::Local;
+        }
+
+        self.save_dir = save_dir.into();
+    }
+
+    /// Turns a `save_dir` such as `gdrive:aletheia` into rclone storage, mirrored into the default save directory.
+    pub fn resolve_save_dir(&mut self) {
+        if let Some(remote) = rclone_remote(&self.save_dir) {
+            self.storage = StorageBackend::Rclone { remote: remote.to_owned() };
+            self.save_dir = Self::get_save_dir();
+        }
+    }
+
+    /// Per-game exclude patterns are used along with the global ones.
+    pub fn exclude_for(&self, game_name: &str) -> impl Iterator<Item = &String> {
+        self.exclude.iter().chain(self.game_exclude.get(game_name).into_iter().flatten())
+    }
+
+    /// Per-game retention overrides replace the global policy entirely.
+    pub fn retention_for(&self, game_name: &str) -> &RetentionPolicy {
+        self.game_retention.get(game_name).unwrap_or(&self.retention)
+    }
+
     #[cfg(not(target_os = "macos"))]
     pub fn load() -> Self {
         let dir = Self::get_dir();
@@ -59,8 +209,16 @@ impl Config {
         if config_path.exists() {
             let config_file = File::open(&config_path).expect("Failed to read config file.");
             let mut cfg: Self = serde_json::from_reader(&config_file).expect("Failed to parse config file.");
+            cfg.resolve_save_dir();
+
+            // Configs from before devices were told apart
+            if cfg.device_id.is_empty() {
+                cfg.device_id = uuid::Uuid::new_v4().to_string();
+                Self::save(&cfg);
+            }
 
-            if !cfg.save_dir.exists() {
+            // Remote storage creates the directory it's mirrored into
+            if cfg.storage == StorageBackend::Local && !cfg.save_dir.exists() {
                 log::warn!("Save directory does not exist, resetting.");
 
                 cfg.save_dir = Self::get_save_dir();
@@ -85,8 +243,16 @@ impl Config {
         if config_path.exists() {
             let config_file = File::open(&config_path).expect("Failed to read config file.");
             let mut cfg: Self = plist::from_reader(&config_file).expect("Failed to parse config file.");
+            cfg.resolve_save_dir();
+
+            // Configs from before devices were told apart
+            if cfg.device_id.is_empty() {
+                cfg.device_id = uuid::Uuid::new_v4().to_string();
+                Self::save(&cfg);
+            }
 
-            if !cfg.save_dir.exists() {
+            // Remote storage creates the directory it's mirrored into
+            if cfg.storage == StorageBackend::Local && !cfg.save_dir.exists() {
                 log::warn!("Save directory does not exist, resetting.");
 
                 cfg.save_dir = Self::get_save_dir();
@@ -124,7 +290,17 @@ impl Default for Config {
         Self {
             custom_databases: vec![],
             save_dir: Self::get_save_dir(),
+            storage: StorageBackend::Local,
             steam_account_id: None,
+            device_id: uuid::Uuid::new_v4().to_string(),
+            deduplicate: false,
+            compression: Compression::None,
+            game_compression: HashMap::new(),
+            encryption: None,
+            retention: RetentionPolicy::default(),
+            game_retention: HashMap::new(),
+            exclude: vec![],
+            game_exclude: HashMap::new(),
             #[cfg(feature = "updater")]
             check_for_updates: true
         }
@@ -5,7 +5,7 @@ use std::env::{home_dir, var_os};
 use std::fs::read_dir;
 use std::path::{Path, PathBuf};
 
-#[cfg(all(unix, not(target_os = "macos")))]
+#[cfg(unix)]
 use std::ffi::OsString;
 
 #[cfg(target_os = "macos")]
@@ -97,6 +97,109 @@ fn path_contains_subpath(haystack: &Path, needle: &str) -> bool {
     haystack.ancestors().any(|ancestor| ancestor.ends_with(needle))
 }
 
+/// The Windows user a prefix keeps its saves under.
+/// Proton always names it steamuser, including in prefixes Heroic or Lutris made with it.
+#[cfg(unix)]
+fn prefix_user(prefix: &Path) -> OsString {
+    let steam_user = OsString::from("steamuser");
+
+    if prefix.join("drive_c/users").join(&steam_user).is_dir() {
+        return steam_user;
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    if path_contains_subpath(prefix, "Steam/steamapps/compatdata") {
+        return steam_user;
+    }
+
+    var_os("USER").unwrap()
+}
+
+/// A directory in `dir`, with the casing it has on disk when there's one that only differs in case.
+/// Windows paths are case-insensitive, so games in a prefix may not use the casing the game database does.
+#[cfg(unix)]
+pub fn existing_case(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+
+    if path.exists() {
+        return path;
+    }
+
+    read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find(|entry| entry.file_name().to_str().is_some_and(|entry_name| entry_name.eq_ignore_ascii_case(name)))
+        .map_or(path, |entry| entry.path())
+}
+
+/// `path` with each file and directory under `base` in the casing it has on disk, so restoring into a prefix
+/// writes to the directories that are there instead of ones that only differ in case.
+#[cfg(unix)]
+pub fn existing_path(path: PathBuf, base: &Path) -> PathBuf {
+    let Ok(relative) = path.strip_prefix(base) else {
+        return path;
+    };
+
+    relative.components().fold(base.to_owned(), |dir, component| existing_case(&dir, &component.as_os_str().to_string_lossy()))
+}
+
+/// Makes the part of a glob pattern after `base` match paths in any casing.
+/// The glob crate only compares names without wildcards case-sensitively, so letters are turned into classes like `[sS]`.
+pub fn ignore_case(pattern: &str, base: &Path) -> String {
+    let base = base.to_string_lossy();
+    let Some(rest) = pattern.strip_prefix(base.as_ref()) else {
+        return pattern.to_owned();
+    };
+
+    let mut result = base.into_owned();
+    let mut in_class = false;
+
+    for c in rest.chars() {
+        match c {
+            '[' => in_class = true,
+            ']' => in_class = false,
+            c if c.is_ascii_alphabetic() && !in_class => {
+                result.extend(['[', c.to_ascii_lowercase(), c.to_ascii_uppercase(), ']']);
+                continue;
+            }
+            _ => {}
+        }
+
+        result.push(c);
+    }
+
+    result
+}
+
+/// Where exports of the registry keys in a prefix are kept, so they're compared and stored like saves without writing to it.
+/// The directory is named after the prefix, as a game can be installed in more than one.
+#[cfg(unix)]
+fn registry_exports(prefix: &Path) -> PathBuf {
+    let hash = blake3::hash(prefix.as_os_str().as_encoded_bytes()).to_hex();
+
+    cache().join("registry").join(&hash[..16])
+}
+
+/// Where Windows keeps each placeholder's directory in a prefix, ordered so paths are shrunk to the most specific one.
+#[cfg(unix)]
+fn prefix_replacements(prefix: &Path) -> Vec<(&'static str, PathBuf)> {
+    let users = existing_case(&existing_case(prefix, "drive_c"), "users");
+    let user = existing_case(&users, &prefix_user(prefix).to_string_lossy());
+    let windows_app_data = existing_case(&user, "AppData");
+    let local_app_data = existing_case(&windows_app_data, "Local");
+
+    vec![
+        ("{WineRegistry}", registry_exports(prefix)),
+        ("{LocalLow}", existing_case(&windows_app_data, "LocalLow")),
+        ("{LocalAppData}", local_app_data.clone()),
+        ("{AppData}", existing_case(&windows_app_data, "Roaming")),
+        ("{Documents}", existing_case(&user, "Documents")),
+        ("{Home}", user),
+        ("{GOGAppData}", local_app_data.join("GOG.com/Galaxy/Applications")),
+    ]
+}
+
 #[cfg(all(unix, not(target_os = "macos")))]
 pub fn expand_path(path: &Path, installation_dir: Option<&Path>, prefix: Option<&Path>, steam_account_id: Option<&str>) -> PathBuf {
     let mut replacements: Vec<(&str, PathBuf)> = vec![];
@@ -108,29 +211,11 @@ pub fn expand_path(path: &Path, installation_dir: Option<&Path>, prefix: Option<
     let linux_app_data = app_data();
 
     if let Some(wine_prefix) = prefix {
-        let username = if path_contains_subpath(wine_prefix, "Steam/steamapps/compatdata") {
-            OsString::from("steamuser")
-        } else {
-            var_os("USER").unwrap()
-        };
-
-        let drive_c = wine_prefix.join("drive_c");
-        let user = drive_c.join("users").join(username);
-        let windows_app_data = user.join("AppData");
-        let documents = user.join("Documents");
-
         let steam_user_data = steam_account_id
             .map_or_else(|| linux_app_data.join("Steam/userdata/[0-9]*"), |id| linux_app_data.join("Steam/userdata").join(id));
 
-        replacements.extend([
-            ("{AppData}", windows_app_data.join("Roaming")),
-            ("{Documents}", documents),
-            ("{Home}", user),
-            ("{LocalAppData}", windows_app_data.join("Local")),
-            ("{LocalLow}", windows_app_data.join("LocalLow")),
-            ("{GOGAppData}", windows_app_data.join("Local").join("GOG.com/Galaxy/Applications")),
-            ("{SteamUserData}", steam_user_data)
-        ]);
+        replacements.extend(prefix_replacements(wine_prefix));
+        replacements.push(("{SteamUserData}", steam_user_data));
     }
 
     replacements.extend([("{XDGConfig}", config()), ("{XDGData}", linux_app_data)]);
@@ -187,21 +272,7 @@ pub fn expand_path(path: &Path, installation_dir: Option<&Path>, prefix: Option<
     replacements.push(("{SteamUserData}", steam_user_data));
 
     if let Some(wine_prefix) = prefix {
-        let username = var_os("USER").unwrap();
-
-        let drive_c = wine_prefix.join("drive_c");
-        let user = drive_c.join("users").join(username);
-        let windows_app_data = user.join("AppData");
-        let documents = user.join("Documents");
-
-        replacements.extend([
-            ("{AppData}", windows_app_data.join("Roaming")),
-            ("{Documents}", documents),
-            ("{Home}", user),
-            ("{LocalAppData}", windows_app_data.join("Local")),
-            ("{LocalLow}", windows_app_data.join("LocalLow")),
-            ("{GOGAppData}", windows_app_data.join("Local").join("GOG.com/Galaxy/Applications"))
-        ]);
+        replacements.extend(prefix_replacements(wine_prefix));
     } else {
         replacements.extend([
             ("{AppData}", application_support.clone()),
@@ -225,28 +296,11 @@ pub fn shrink_path(path: &Path, installation_dir: Option<&Path>, prefix: Option<
     let linux_app_data = app_data();
 
     if let Some(wine_prefix) = prefix {
-        let username = if path_contains_subpath(wine_prefix, "Steam/steamapps/compatdata") {
-            OsString::from("steamuser")
-        } else {
-            var_os("USER").unwrap()
-        };
-
-        let drive_c = wine_prefix.join("drive_c");
-        let user = drive_c.join("users").join(username);
-        let windows_app_data = user.join("AppData");
-
         let steam_user_data = steam_account_id
             .map_or_else(|| linux_app_data.join("Steam/userdata/[0-9]*"), |id| linux_app_data.join("Steam/userdata").join(id));
 
-        replacements.extend([
-            ("{LocalLow}", windows_app_data.join("LocalLow")),
-            ("{LocalAppData}", windows_app_data.join("Local")),
-            ("{AppData}", windows_app_data.join("Roaming")),
-            ("{Documents}", user.join("Documents")),
-            ("{Home}", user),
-            ("{GOGAppData}", windows_app_data.join("Local").join("GOG.com/Galaxy/Applications")),
-            ("{SteamUserData}", steam_user_data)
-        ]);
+        replacements.extend(prefix_replacements(wine_prefix));
+        replacements.push(("{SteamUserData}", steam_user_data));
     }
 
     replacements.extend([("{XDGConfig}", config()), ("{XDGData}", linux_app_data)]);
@@ -303,20 +357,7 @@ pub fn shrink_path(path: &Path, installation_dir: Option<&Path>, prefix: Option<
     replacements.push(("{SteamUserData}", steam_user_data));
 
     if let Some(wine_prefix) = prefix {
-        let username = var_os("USER").unwrap();
-
-        let drive_c = wine_prefix.join("drive_c");
-        let user = drive_c.join("users").join(username);
-        let windows_app_data = user.join("AppData");
-
-        replacements.extend([
-            ("{LocalLow}", windows_app_data.join("LocalLow")),
-            ("{LocalAppData}", windows_app_data.join("Local")),
-            ("{AppData}", windows_app_data.join("Roaming")),
-            ("{Documents}", user.join("Documents")),
-            ("{Home}", user),
-            ("{GOGAppData}", windows_app_data.join("Local").join("GOG.com/Galaxy/Applications"))
-        ]);
+        replacements.extend(prefix_replacements(wine_prefix));
     } else {
         replacements.extend([
             ("{AppData}", application_support.clone()),
@@ -404,6 +445,44 @@ mod tests {
         }
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_proton_prefix_user() {
+        let prefix = std::env::temp_dir().join("aletheia_prefix_user_test");
+        let user = prefix.join("drive_c/users/steamuser");
+        let save_file = Path::new("{Documents}/My Games/Terraria/Players/UnitTest.plr");
+
+        std::fs::create_dir_all(&user).unwrap();
+
+        assert_eq!(expand_path(save_file, None, Some(&prefix), None), user.join("Documents/My Games/Terraria/Players/UnitTest.plr"));
+        assert_eq!(shrink_path(&user.join("Documents/My Games/Terraria/Players/UnitTest.plr"), None, Some(&prefix), None), save_file);
+
+        std::fs::remove_dir_all(&prefix).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prefix_casing() {
+        let prefix = std::env::temp_dir().join("aletheia_prefix_casing_test");
+        let saves = prefix.join("drive_c/users/steamuser/documents/my games/Unit Test");
+        let save_file = Path::new("{Documents}/My Games/Unit Test/Save1.sav");
+
+        std::fs::create_dir_all(&saves).unwrap();
+        std::fs::write(saves.join("save1.sav"), "").unwrap();
+
+        let expanded = expand_path(save_file, None, Some(&prefix), None);
+
+        assert_eq!(expanded, prefix.join("drive_c/users/steamuser/documents/My Games/Unit Test/Save1.sav"));
+        assert_eq!(existing_path(expanded, &prefix), saves.join("save1.sav"));
+        assert_eq!(
+            shrink_path(&saves.join("save1.sav"), None, Some(&prefix), None),
+            Path::new("{Documents}/my games/Unit Test/save1.sav")
+        );
+        assert_eq!(ignore_case("/Saves/[0-9]*.sav", Path::new("/Saves")), "/Saves/[0-9]*.[sS][aA][vV]");
+
+        std::fs::remove_dir_all(&prefix).unwrap();
+    }
+
     #[test]
     fn test_path_shrinkage() {
         let home_dir = home();
@@ -2,16 +2,54 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 use blake3::Hasher;
-use std::fs::File;
-use std::io::{BufReader, copy};
+use std::fs::{File, Metadata};
+use std::io::{BufReader, Read, copy};
 use std::path::Path;
+use std::time::SystemTime;
+
+#[cfg(unix)]
+use std::fs::{Permissions, set_permissions};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
 
 pub fn hash_file(file_path: &Path) -> String {
     let file = File::open(file_path).unwrap();
-    let mut reader = BufReader::new(file);
+
+    hash_reader(BufReader::new(file)).unwrap()
+}
+
+pub fn hash_reader(mut reader: impl Read) -> std::io::Result<String> {
     let mut hasher = Hasher::new();
 
-    copy(&mut reader, &mut hasher).unwrap();
+    copy(&mut reader, &mut hasher)?;
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Permission bits of a file, which aren't recorded on Windows.
+#[cfg(unix)]
+#[expect(clippy::unnecessary_wraps, reason = "Matches the Windows version, which has no permission bits to record")]
+pub fn file_mode(metadata: &Metadata) -> Option<u32> {
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(windows)]
+pub const fn file_mode(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+/// Sets the modification time of a file, and its permission bits when given on Unix.
+/// Permissions are set last, as they can make the file read-only.
+pub fn set_metadata(path: &Path, modified: SystemTime, mode: Option<u32>) -> std::io::Result<()> {
+    File::options().write(true).open(path)?.set_modified(modified)?;
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        set_permissions(path, Permissions::from_mode(mode))?;
+    }
+
+    #[cfg(windows)]
+    let _ = mode;
 
-    hasher.finalize().to_hex().to_string()
+    Ok(())
 }
@@ -3,11 +3,13 @@
 
 use crate::config::Config;
 use crate::dirs::cache;
-use crate::scanner::{Game, Scanner};
+use crate::scanner::{Game, Scanner, StoreId};
 use crate::scanner::{HeroicScanner, SteamScanner};
+use glob::Pattern;
 use reqwest::{StatusCode, header};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::{File, create_dir_all, read_to_string, write};
 
 #[cfg(all(unix, not(target_os = "macos")))]
@@ -33,22 +35,195 @@ pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GameDbEntry {
-    pub files: GameFiles
+    pub files: GameFiles,
+    /// Saves are the same on every platform, and each platform's paths are listed in the same order.
+    /// This lets a backup made on one platform be restored where the game keeps its saves on another.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub equivalent: bool,
+    #[serde(default, skip_serializing_if = "StoreIds::is_empty")]
+    pub ids: StoreIds,
+    /// Other names the game is installed under, like older or localized titles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Paths match saves in any casing on every platform, for games that don't keep the casing they were given.
+    /// Paths inside a Wine prefix always do, as Windows ignores case.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignore_case: bool,
+    /// Registry keys the game keeps saves or settings in, like `HKCU\Software\Game`, which are backed up with their subkeys.
+    /// Only games running in a Wine prefix have them backed up.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registry: Vec<String>
 }
 
+/// The game's IDs in each store, which installed games are matched by before their names.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct StoreIds {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steam: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gog: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amazon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lutris: Option<String>
+}
+
+impl StoreIds {
+    pub const fn is_empty(&self) -> bool {
+        self.steam.is_none() && self.gog.is_none() && self.epic.is_none() && self.amazon.is_none() && self.lutris.is_none()
+    }
+
+    fn matches(&self, id: &StoreId) -> bool {
+        match id {
+            StoreId::Steam(id) => self.steam == Some(*id),
+            StoreId::Gog(id) => self.gog == Some(*id),
+            StoreId::Epic(id) => self.epic.as_ref() == Some(id),
+            StoreId::Amazon(id) => self.amazon.as_ref() == Some(id),
+            StoreId::Lutris(slug) => self.lutris.as_ref() == Some(slug)
+        }
+    }
+}
+
+/// Every platform's paths are kept, so backups made on another one can be mapped onto this one.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GameFiles {
     pub windows: Option<Vec<String>>,
-    #[cfg(all(unix, not(target_os = "macos")))]
     pub linux: Option<Vec<String>>,
-    #[cfg(target_os = "macos")]
-    pub mac: Option<Vec<String>>
+    pub mac: Option<Vec<String>>,
+    /// Each platform's files that match its paths but aren't saves, like caches or logs, which aren't backed up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Box<Self>>
+}
+
+/// The operating system a snapshot was made on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    Windows,
+    Linux,
+    Mac
+}
+
+impl Platform {
+    pub const ALL: [Self; 3] = [Self::Windows, Self::Linux, Self::Mac];
+
+    pub const fn current() -> Self {
+        #[cfg(windows)]
+        return Self::Windows;
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        return Self::Linux;
+
+        #[cfg(target_os = "macos")]
+        return Self::Mac;
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Windows => "Windows",
+            Self::Linux => "Linux",
+            Self::Mac => "macOS"
+        })
+    }
+}
+
+impl GameFiles {
+    pub fn for_platform(&self, platform: Platform) -> Option<&[String]> {
+        match platform {
+            Platform::Windows => self.windows.as_deref(),
+            Platform::Linux => self.linux.as_deref(),
+            Platform::Mac => self.mac.as_deref()
+        }
+    }
 }
 
-#[derive(Deserialize, Serialize)]
+impl GameDbEntry {
+    /// Maps a path backed up on another platform to where `platform` keeps the same file, if the saves are equivalent.
+    pub fn translate(&self, path: &str, platform: Platform) -> Option<String> {
+        if !self.equivalent {
+            return None;
+        }
+
+        let targets = self.files.for_platform(platform)?;
+
+        Platform::ALL
+            .into_iter()
+            .filter(|other| *other != platform)
+            .filter_map(|other| self.files.for_platform(other))
+            .flat_map(|patterns| patterns.iter().zip(targets))
+            .find_map(|(pattern, target)| {
+                let matches = Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(path));
+                let rest = path.strip_prefix(literal_dir(pattern)).filter(|_| matches)?;
+                let translated = format!("{}{rest}", literal_dir(target));
+
+                Pattern::new(target).is_ok_and(|target| target.matches(&translated)).then_some(translated)
+            })
+    }
+}
+
+/// The part of a pattern up to the last directory before any wildcard.
+fn literal_dir(pattern: &str) -> &str {
+    let literal = pattern.find(['*', '?', '[']).map_or(pattern, |wildcard| &pattern[..wildcard]);
+    literal.rfind('/').map_or("", |slash| &pattern[..=slash])
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct GameInfo {
     pub name: String,
-    pub files: Vec<FileMetadata>
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<std::time::SystemTime>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub layout: Layout,
+    /// The device that made the snapshot, see [`Config::device_id`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// The snapshot this one was made on top of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Local saves kept aside when a conflict was resolved by keeping both. Never picked as the latest snapshot.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub conflict: bool,
+    /// Where the snapshot was made, which is unknown for snapshots made before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    pub files: Vec<FileMetadata>,
+    /// Paths that didn't exist when the snapshot was made, which restoring it removes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deleted: Vec<String>,
+    /// The install a pre-restore snapshot's saves were replaced in, unknown for ones kept before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_to: Option<Install>
+}
+
+/// An install of a game, told apart from others of the same game by its launcher and prefix.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Install {
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<std::path::PathBuf>
+}
+
+/// How files are laid out inside a snapshot directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// Stored by file name only, which was used before directory structure was preserved.
+    #[default]
+    Flat,
+    /// Stored under their shrunk path, so files with the same name in different directories don't collide.
+    Tree,
+    /// Stored once under their hash in a store shared by every snapshot and game.
+    Blobs
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -56,7 +231,40 @@ pub struct FileMetadata {
     pub hash: String,
     pub modified: std::time::SystemTime,
     pub path: String,
-    pub size: u64
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Compression::is_none")]
+    pub compression: Compression,
+    /// Size of the stored copy, only recorded for compressed or encrypted files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+    /// Unix permission bits, which restoring sets the file back to. Not recorded on Windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd
+}
+
+impl Compression {
+    #[expect(clippy::trivially_copy_pass_by_ref, reason = "Serde's skip_serializing_if passes a reference")]
+    pub const fn is_none(&self) -> bool {
+        matches!(self, Self::None)
+    }
+
+    /// Appended to the names of compressed files so they're never mistaken for the original.
+    pub const fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Zstd => Some("zst")
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -105,19 +313,28 @@ pub fn get_installed_games() -> Vec<Game> {
     games
         .into_iter()
         .filter_map(|mut game| {
-            if db.contains_key(&game.name) {
-                return Some(game);
-            }
-
-            let clean_name = game.name.replace("™", "").replace("®", "").trim().to_owned();
-            db.contains_key(&clean_name).then(|| {
-                game.name = clean_name;
-                game
-            })
+            game.name = find_entry(&db, &game)?.to_owned();
+            Some(game)
         })
         .collect()
 }
 
+/// The name of the game's entry, matched by store ID, then by alias and then by name.
+fn find_entry<'a>(db: &'a HashMap<String, GameDbEntry>, game: &Game) -> Option<&'a str> {
+    let name = normalize_name(&game.name);
+
+    let by_id = game.store_id.as_ref().and_then(|id| db.iter().find(|(_, entry)| entry.ids.matches(id)));
+    let by_alias = || db.iter().find(|(_, entry)| entry.aliases.iter().any(|alias| normalize_name(alias) == name));
+    let by_name = || db.get_key_value(&game.name).or_else(|| db.iter().find(|(key, _)| normalize_name(key) == name));
+
+    by_id.or_else(by_alias).or_else(by_name).map(|(key, _)| key.as_str())
+}
+
+/// Names differ between stores in trademark symbols and apostrophes.
+fn normalize_name(name: &str) -> String {
+    name.replace(['™', '®'], "").replace('’', "'").trim().to_owned()
+}
+
 pub fn update() -> Result<bool> {
     let cache_dir = cache();
 
@@ -206,3 +423,74 @@ pub fn update_custom(cfg: &Config) -> Result<bool> {
 
     Ok(updated)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(name: &str, store_id: Option<StoreId>) -> Game {
+        Game {
+            name: name.into(),
+            installation_dir: None,
+            #[cfg(unix)]
+            prefix: None,
+            source: "Test".into(),
+            store_id
+        }
+    }
+
+    #[test]
+    fn test_find_entry() {
+        let db: HashMap<String, GameDbEntry> = serde_yaml::from_str(
+            r#"
+"AKIBA’S TRIP: Hellbound & Debriefed":
+  files:
+    windows:
+      - "{GameRoot}/AkibaHD_Data/SaveData/*.bytes"
+Half-Life 2:
+  aliases:
+    - Half-Life 2 (2004)
+  files:
+    windows:
+      - "{GameRoot}/hl2/save/*"
+  ids:
+    epic: Eagle
+    gog: 1207658889
+    steam: 220
+"#
+        )
+        .unwrap();
+
+        // Entries without IDs or aliases still parse
+        assert!(db["AKIBA’S TRIP: Hellbound & Debriefed"].ids.is_empty());
+
+        assert_eq!(find_entry(&db, &game("HL2", Some(StoreId::Steam(220)))), Some("Half-Life 2"));
+        assert_eq!(find_entry(&db, &game("Half-Life 2 (2004)", Some(StoreId::Steam(1)))), Some("Half-Life 2"));
+        assert_eq!(find_entry(&db, &game("Half-Life 2™", None)), Some("Half-Life 2"));
+        assert_eq!(find_entry(&db, &game("AKIBA'S TRIP: Hellbound & Debriefed", None)), Some("AKIBA’S TRIP: Hellbound & Debriefed"));
+        assert_eq!(find_entry(&db, &game("HL2", Some(StoreId::Epic("Eagle".into())))), Some("Half-Life 2"));
+        assert_eq!(find_entry(&db, &game("Half-Life", Some(StoreId::Gog(1)))), None);
+        assert_eq!(find_entry(&db, &game("Half-Life", Some(StoreId::Amazon("Eagle".into())))), None);
+    }
+
+    #[test]
+    fn test_exclude() {
+        let entry: GameDbEntry = serde_yaml::from_str(
+            r#"
+files:
+  exclude:
+    windows:
+      - "{LocalAppData}/Remedy/AlanWake2/*.log"
+  windows:
+    - "{LocalAppData}/Remedy/AlanWake2/*"
+"#
+        )
+        .unwrap();
+        let exclude = entry.files.exclude.as_deref().unwrap();
+
+        assert_eq!(entry.files.for_platform(Platform::Windows).unwrap(), ["{LocalAppData}/Remedy/AlanWake2/*"]);
+        assert_eq!(exclude.for_platform(Platform::Windows).unwrap(), ["{LocalAppData}/Remedy/AlanWake2/*.log"]);
+        assert!(exclude.for_platform(Platform::Linux).is_none());
+        assert!(exclude.exclude.is_none());
+    }
+}
@@ -5,7 +5,7 @@ use crate::config::Config;
 use crate::gamedb;
 use crate::infer::Launcher;
 use crate::infer::launchers::Heroic;
-use crate::operations::backup_game;
+use crate::operations::{BackupOptions, backup_game, load_key, pull_storage};
 
 #[cfg(all(unix, not(target_os = "macos")))]
 use crate::infer::launchers::Lutris;
@@ -24,10 +24,33 @@ pub fn backup(launcher: &str, config: &Config) {
     let game_db = gamedb::parse();
 
     if let Some(game) = game {
-        if let Err(e) = backup_game(&game, config, &game_db[&game.name]) {
+        let mut mirror = match pull_storage(config) {
+            Ok(mirror) => mirror,
+            Err(e) => {
+                log::error!("Failed to download backups: {e}");
+                return;
+            }
+        };
+
+        let key = match load_key(config, None) {
+            Ok(key) => key,
+            Err(e) => {
+                log::error!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) = backup_game(&game, config, &game_db[&game.name], key.as_ref(), BackupOptions::default()) {
             log::error!("Failed to backup {}: {}", game.name, e);
-        } else {
-            log::info!("Backed up {}.", game.name);
+            return;
+        }
+
+        log::info!("Backed up {}.", game.name);
+
+        if let Some(mirror) = &mut mirror
+            && let Err(e) = mirror.push()
+        {
+            log::error!("Failed to upload backups: {e}");
         }
     }
 }
@@ -5,9 +5,8 @@ use crate::config::Config;
 use crate::gamedb;
 use crate::infer::Launcher;
 use crate::infer::launchers::Heroic;
-use crate::operations::restore_game;
+use crate::operations::{RestoreOptions, latest_snapshot, load_key, pull_storage, restore_game};
 use crate::utils;
-use std::fs::File;
 
 #[cfg(all(unix, not(target_os = "macos")))]
 use crate::infer::launchers::Lutris;
@@ -24,6 +23,14 @@ pub fn restore(launcher: &str, config: &Config) {
     };
 
     if let Some(game) = game {
+        let mut mirror = match pull_storage(config) {
+            Ok(mirror) => mirror,
+            Err(e) => {
+                log::error!("Failed to download backups: {e}");
+                return;
+            }
+        };
+
         let game_dir = config.save_dir.join(utils::sanitize_game_name(&game.name).as_ref());
 
         if !game_dir.exists() || !game_dir.is_dir() {
@@ -31,22 +38,41 @@ pub fn restore(launcher: &str, config: &Config) {
             return;
         }
 
-        let manifest_path = game_dir.join("aletheia_manifest.yaml");
+        let key = match load_key(config, None) {
+            Ok(key) => key,
+            Err(e) => {
+                log::error!("Failed to unlock backups: {e}");
+                return;
+            }
+        };
+
+        let snapshot = match latest_snapshot(&game_dir, key.as_ref()) {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => {
+                log::warn!("No backups found for {}.", game.name);
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to read {}'s snapshots: {e}", game.name);
+                return;
+            }
+        };
+
+        let game_db = gamedb::parse();
+        let options = RestoreOptions { source: Some(&game.source), entry: game_db.get(&game.name), ..RestoreOptions::default() };
 
-        if !manifest_path.exists() {
-            log::error!("{} is missing a manifest file.", game.name);
+        if let Err(e) = restore_game(&snapshot, &gamedb::get_installed_games(), config, key.as_ref(), options) {
+            log::error!("Failed to restore {}: {}", game.name, e);
             return;
         }
 
-        let Ok(manifest) = serde_yaml::from_reader::<File, gamedb::GameInfo>(File::open(manifest_path).unwrap()) else {
-            log::error!("Failed to parse {}'s manifest.", game_dir.file_name().unwrap().display());
-            return;
-        };
+        log::info!("Restore up {}.", game.name);
 
-        if let Err(e) = restore_game(&game_dir, &manifest, &gamedb::get_installed_games(), config) {
-            log::error!("Failed to restore {}: {}", game.name, e);
-        } else {
-            log::info!("Restore up {}.", game.name);
+        // Restoring records what this device synced with, which other devices compare against
+        if let Some(mirror) = &mut mirror
+            && let Err(e) = mirror.push()
+        {
+            log::error!("Failed to upload backups: {e}");
         }
     }
 }
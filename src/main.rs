@@ -49,6 +49,10 @@ fn main() {
     log::info!("Aletheia v{} (Windows)", env!("CARGO_PKG_VERSION"));
 
     let config = config::Config::load();
+
+    if let Err(e) = operations::migrate_backups(&config.save_dir) {
+        log::error!("Failed to migrate backups made by an older version: {e}");
+    }
     let mut args = std::env::args().skip(1);
 
     if let Some(cmd) = args.next() {
@@ -56,6 +60,12 @@ fn main() {
         match cmd.as_str() {
             "backup" => commands::Backup::run(args, &config),
             "restore" => commands::Restore::run(args, &config),
+            "undo_restore" => commands::UndoRestore::run(args, &config),
+            "prune" => commands::Prune::run(args, &config),
+            "pin" => commands::Pin::run(args, &config),
+            "unpin" => commands::Unpin::run(args, &config),
+            "rotate_key" => commands::RotateKey::run(args, &config),
+            "snapshots" => commands::Snapshots::run(args, &config),
             #[cfg(all(feature = "updater", not(debug_assertions)))]
             "update" => commands::Update::run(args, &config),
             "update_gamedb" => commands::UpdateGameDb::run(args, &config),
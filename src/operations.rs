@@ -2,7 +2,29 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 mod backup;
+mod blob;
+mod codec;
+mod conflict;
+mod encryption;
+mod history;
+mod plan;
+mod prune;
+#[cfg(unix)]
+mod registry;
 mod restore;
+mod rotate;
+mod snapshot;
+mod storage;
 
-pub use backup::backup_game;
-pub use restore::restore_game;
+pub use backup::{BackupOptions, Error as BackupError, backup_game};
+pub use blob::collect_garbage;
+pub use conflict::{Conflict, Resolution, resolve_conflict};
+pub use encryption::{Error as EncryptionError, Key, PASSPHRASE_VAR, load_key, read_secret};
+pub use plan::{Plan, Reason};
+pub use prune::prune_game;
+pub use restore::{Error as RestoreError, LocalChanges, RestoreOptions, restore_game, undo_restore};
+pub use rotate::rotate_key;
+pub use snapshot::{
+    Snapshot, SnapshotSelector, latest_snapshot, list_snapshots, migrate_backups, parse_time, select_snapshot, write_manifest
+};
+pub use storage::pull_storage;
@@ -1,135 +1,425 @@
 // SPDX-FileCopyrightText: 2025 Spencer
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::codec::{encode_file, encoded_path};
+use super::conflict::{Conflict, Resolution};
+use super::encryption::{self, Key};
+use super::history::{self, History};
+use super::plan::{Action, Plan, Reason};
+use super::restore::local_path;
+use super::snapshot::Snapshot;
+use super::{blob, snapshot};
 use crate::config::Config;
-use crate::dirs::{expand_path, shrink_path};
-use crate::file::hash_file;
-use crate::gamedb::{FileMetadata, GameDbEntry, GameInfo};
+use crate::dirs::{self, expand_path, shrink_path};
+use crate::file::{file_mode, hash_file, set_metadata};
+use crate::gamedb::{Compression, FileMetadata, GameDbEntry, GameInfo, Layout, Platform};
 use crate::scanner::Game;
 use crate::utils;
-use glob::glob;
-use std::fs::{File, copy, create_dir_all, metadata};
-use std::path::Path;
+use glob::{MatchOptions, Pattern, glob_with};
+use std::collections::{HashMap, HashSet};
+use std::fs::{copy, create_dir_all, hard_link, metadata, read_dir};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[cfg(unix)]
+use super::registry;
+#[cfg(unix)]
+use std::fs::{read_to_string, remove_file, write};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Failed to create backup directory: {0}")]
     DirectoryCreation(#[from] std::io::Error),
-    #[error("Malformed manifest file")]
-    MalformedManifest
+    #[error("Failed to read snapshots: {0}")]
+    Snapshot(#[from] snapshot::Error),
+    #[error("Failed to store file: {0}")]
+    Encryption(#[from] encryption::Error),
+    #[error("Failed to read sync history: {0}")]
+    History(#[from] history::Error),
+    #[error("{0}")]
+    Conflict(Conflict)
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
 
-pub fn backup_game(game: &Game, config: &Config, entry: &GameDbEntry) -> Result<bool> {
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BackupOptions {
+    /// How to settle a conflict with another device, the backup fails with [`Error::Conflict`] when it's needed but not given.
+    pub resolution: Option<Resolution>,
+    /// Only plans the backup, without storing anything or recording what this device synced with.
+    pub dry_run: bool
+}
+
+/// Files and the manifest are encrypted when a key is given.
+/// Fails with [`Error::Conflict`] when another device backed up different saves since this one last synced,
+/// unless `options` say how to settle it.
+pub fn backup_game(game: &Game, config: &Config, entry: &GameDbEntry, key: Option<&Key>, options: BackupOptions) -> Result<Plan> {
     let steam_id = config.steam_account_id.as_deref();
-    let backup_folder = config.save_dir.join(utils::sanitize_game_name(&game.name).as_ref());
-    let manifest_path = backup_folder.join("aletheia_manifest.yaml");
-    let existing_manifest = manifest_path
-        .exists()
-        .then(|| File::open(&manifest_path).unwrap())
-        .map(|file| serde_yaml::from_reader::<File, GameInfo>(file).map_err(|_| Error::MalformedManifest))
-        .transpose()?;
+    let game_dir = config.save_dir.join(utils::sanitize_game_name(&game.name).as_ref());
+    let snapshots = snapshot::list_snapshots(&game_dir, key)?;
+    let previous_snapshot = snapshots.iter().rev().find(|snapshot| !snapshot.manifest.conflict);
+    let (files, excluded) = find_files(game, entry, config);
+    let mut plan = Plan::new(&game.name, None);
+
+    for file in &excluded {
+        log::info!("Excluded {} from backing up {}.", file.display(), game.name);
+        plan.add(file.clone(), Action::Skip, metadata(file).map_or(0, |metadata| metadata.len()), Reason::Excluded);
+    }
+
+    if files.is_empty() {
+        return Ok(plan);
+    }
 
     let mut changed = false;
+    let mut pending = vec![];
+    let mut local = HashMap::new();
+
+    for file in files {
+        let shrunk_file_path = shrunk_path(game, steam_id, &file);
+        let existing = previous_snapshot.and_then(|snapshot| snapshot.manifest.files.iter().find(|m| m.path == shrunk_file_path));
+        let should_backup = existing.is_none_or(|existing| {
+            metadata(&file).unwrap().modified().unwrap() > existing.modified && existing.hash != hash_file(&file)
+        });
+
+        // Copies that aren't encrypted the way new files would be are stored again
+        let reused = existing.filter(|existing| !should_backup && existing.encrypted == key.is_some());
+        let (action, reason) = plan_file(existing.is_some(), reused.is_some(), should_backup);
+
+        changed |= should_backup;
+        plan.add(file.clone(), action, metadata(&file).map_or(0, |metadata| metadata.len()), reason);
+        local.insert(shrunk_file_path.clone(), file.clone());
+        pending.push((snapshot::stored_path(&shrunk_file_path), file, reused));
+    }
+
+    let excluded: Vec<String> = excluded.iter().map(|file| shrunk_path(game, steam_id, file)).collect();
+    let deleted = deleted_since(&mut plan, game, steam_id, previous_snapshot, &local, &excluded);
+    changed |= plan.files.iter().any(|file| file.reason == Reason::DeletedLocally);
+
+    let Some((parent, conflict)) = lineage(game, config, &snapshots, previous_snapshot, &local, options.resolution)? else {
+        plan.skip_all(Reason::Superseded);
+        return Ok(plan);
+    };
+
+    if !changed {
+        plan.skip_all(Reason::Unchanged);
+
+        if let Some(previous) = previous_snapshot.filter(|_| !options.dry_run) {
+            history::mark_synced(config, &game.name, Some(&previous.id))?;
+        }
+
+        return Ok(plan);
+    }
+
+    if options.dry_run {
+        return Ok(plan);
+    }
+
+    let created = SystemTime::now();
+    let (id, snapshot_dir) = snapshot::create(&game_dir, created)?;
     let mut game_files: Vec<FileMetadata> = vec![];
-    let mut paths = vec![];
 
-    #[cfg(windows)]
-    if let Some(ref windows_paths) = entry.files.windows {
-        paths.extend(windows_paths);
+    let layout = if config.deduplicate {
+        Layout::Blobs
+    } else {
+        Layout::Tree
+    };
+    let compression = config.compression_for(&game.name);
+
+    for (stored_path, file, existing) in pending {
+        // Unchanged files are already encoded, so they're copied as is
+        let (source, mut file_metadata) = match existing.zip(previous_snapshot) {
+            Some((existing, previous)) => (previous.file_path(existing), existing.to_owned()),
+            None => (file.clone(), FileMetadata { compression, encrypted: key.is_some(), ..process_file(&file, game, steam_id) })
+        };
+        let modified = file_metadata.modified;
+        let encode = |dest: &Path| store_file(&source, dest, existing.is_some(), modified, compression, key);
+
+        let stored_size = if layout == Layout::Blobs {
+            blob::store_blob(&config.save_dir, &file_metadata, |dest| encode(dest).map(|_| ()))?
+        } else {
+            let dest = encoded_path(snapshot_dir.join(stored_path), &file_metadata);
+
+            create_dir_all(dest.parent().unwrap())?;
+
+            // Unchanged files are shared with the previous snapshot instead of being copied again
+            if existing.is_some() && hard_link(&source, &dest).is_ok() {
+                dest.metadata()?.len()
+            } else {
+                encode(&dest)?
+            }
+        };
+
+        if !file_metadata.compression.is_none() || file_metadata.encrypted {
+            file_metadata.stored_size = Some(stored_size);
+        }
+
+        game_files.push(file_metadata);
     }
 
-    #[cfg(unix)]
-    if game.prefix.is_some()
-        && let Some(ref windows_paths) = entry.files.windows
-    {
-        paths.extend(windows_paths);
+    let game_metadata = GameInfo {
+        name: game.name.clone(),
+        created: Some(created),
+        pinned: false,
+        tag: None,
+        layout,
+        device: Some(config.device_id.clone()),
+        hostname: history::hostname(),
+        parent,
+        conflict,
+        platform: Some(Platform::current()),
+        files: game_files,
+        deleted,
+        restored_to: None
+    };
+
+    snapshot::write_manifest(&snapshot_dir, &game_metadata, key)?;
+
+    // Conflict copies are set aside, the saves here are synced once the other device's are restored
+    if !conflict {
+        history::mark_synced(config, &game.name, Some(&id))?;
     }
 
-    #[cfg(all(unix, not(target_os = "macos")))]
-    if let Some(ref linux_paths) = entry.files.linux {
-        paths.extend(linux_paths);
+    plan.snapshot = Some(id);
+
+    Ok(plan)
+}
+
+/// Copies a file that's already encoded as is, or encodes it, and returns the size of the stored copy.
+/// Stored copies keep the save's modification time, while its permissions are only recorded so they stay readable.
+fn store_file(
+    source: &Path, dest: &Path, encoded: bool, modified: SystemTime, compression: Compression, key: Option<&Key>
+) -> encryption::Result<u64> {
+    let stored_size = if encoded {
+        copy(source, dest)?
+    } else {
+        encode_file(source, dest, compression, key)?
+    };
+
+    set_metadata(dest, modified, None)?;
+    Ok(stored_size)
+}
+
+fn plan_file(backed_up: bool, reused: bool, should_backup: bool) -> (Action, Reason) {
+    match (backed_up, reused) {
+        (false, _) => (Action::Copy, Reason::New),
+        (true, _) if should_backup => (Action::Copy, Reason::Changed),
+        (true, false) => (Action::Copy, Reason::EncryptionChanged),
+        (true, true) => (Action::Skip, Reason::Unchanged)
     }
+}
 
-    #[cfg(target_os = "macos")]
-    if let Some(ref mac_paths) = entry.files.mac {
-        paths.extend(mac_paths);
+/// Picks the snapshot a new one is made on top of and whether it's a conflict copy, or `None` when nothing should be backed up.
+fn lineage(
+    game: &Game, config: &Config, snapshots: &[Snapshot], previous: Option<&Snapshot>, local: &HashMap<String, PathBuf>,
+    resolution: Option<Resolution>
+) -> Result<Option<(Option<String>, bool)>> {
+    let Some(previous) = previous else {
+        return Ok(Some((None, false)));
+    };
+
+    let synced = history::synced_snapshot(config, &game.name)?;
+    let unchanged_since = |snapshot: &Snapshot| {
+        snapshot.manifest.files.len() == local.len() && history::matches_local(snapshot, |path| local.get(path).cloned())
+    };
+
+    match history::compare(snapshots, synced.as_deref(), previous, &config.device_id, true, unchanged_since) {
+        History::Linear => Ok(Some((Some(previous.id.clone()), false))),
+        History::Behind => {
+            log::warn!(
+                "Skipped backing up {} as {} has newer saves that haven't been restored here.",
+                game.name,
+                previous.manifest.hostname.as_deref().unwrap_or("another device")
+            );
+            Ok(None)
+        }
+        History::Diverged => match resolution {
+            None => Err(Error::Conflict(Conflict {
+                game: game.name.clone(),
+                snapshot: previous.id.clone(),
+                hostname: previous.manifest.hostname.clone()
+            })),
+            Some(Resolution::Local) => Ok(Some((Some(previous.id.clone()), false))),
+            Some(Resolution::Remote) => Ok(None),
+            Some(Resolution::Both) => Ok(Some((synced, true)))
+        }
     }
+}
 
-    let mut files = vec![];
+/// Saves that were backed up before but aren't here anymore, including ones recorded as deleted by earlier snapshots.
+/// They're recorded so restoring the new snapshot removes them, and nothing is recorded when no saves are found at all.
+/// Ones deleted since the previous snapshot are added to `plan`. Saves that are excluded now are left out without being deleted.
+fn deleted_since(
+    plan: &mut Plan, game: &Game, steam_id: Option<&str>, previous: Option<&Snapshot>, local: &HashMap<String, PathBuf>,
+    excluded: &[String]
+) -> Vec<String> {
+    let Some(previous) = previous else {
+        return vec![];
+    };
+
+    let mut deleted: Vec<String> = previous
+        .manifest
+        .files
+        .iter()
+        .map(|file| &file.path)
+        .chain(&previous.manifest.deleted)
+        .filter(|path| !local.contains_key(*path) && !excluded.contains(path))
+        .cloned()
+        .collect();
+
+    deleted.sort();
+    deleted.dedup();
+
+    for path in deleted.iter().filter(|path| !previous.manifest.deleted.contains(path)) {
+        plan.add(local_path(game, steam_id, path), Action::Remove, 0, Reason::DeletedLocally);
+    }
+
+    deleted
+}
 
-    for path in paths {
+/// The path a file is recorded under, with placeholders instead of this device's directories.
+pub(super) fn shrunk_path(game: &Game, steam_id: Option<&str>, file: &Path) -> String {
+    #[cfg(unix)]
+    let shrunk = shrink_path(file, game.installation_dir.as_deref(), game.prefix.as_deref(), steam_id);
+
+    #[cfg(windows)]
+    let shrunk = shrink_path(file, game.installation_dir.as_deref(), steam_id);
+
+    shrunk.to_string_lossy().into_owned()
+}
+
+/// Finds the game's saves, returning them and the ones excluded by the game database or `config`.
+pub(super) fn find_files(game: &Game, entry: &GameDbEntry, config: &Config) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let steam_id = config.steam_account_id.as_deref();
+
+    #[cfg(unix)]
+    let prefix = game.prefix.as_deref();
+
+    #[cfg(windows)]
+    let prefix: Option<&Path> = None;
+
+    // Games in a prefix keep their saves where Windows does
+    let platforms = if prefix.is_some() {
+        vec![Platform::Windows, Platform::current()]
+    } else {
+        vec![Platform::current()]
+    };
+    let expand = |path: &str| {
         #[cfg(unix)]
         let expanded = expand_path(Path::new(path), game.installation_dir.as_deref(), game.prefix.as_deref(), steam_id);
 
         #[cfg(windows)]
         let expanded = expand_path(Path::new(path), game.installation_dir.as_deref(), steam_id);
 
-        let found_paths = glob(&expanded.to_string_lossy()).unwrap();
-
-        for file in found_paths {
-            let file = file.unwrap();
+        expanded.to_string_lossy().into_owned()
+    };
+
+    let exclude: Vec<Pattern> = platforms
+        .iter()
+        .filter_map(|platform| entry.files.exclude.as_ref()?.for_platform(*platform))
+        .flatten()
+        .chain(config.exclude_for(&game.name))
+        .filter_map(|pattern| {
+            Pattern::new(&expand(pattern))
+                .inspect_err(|e| log::warn!("Ignored invalid exclude pattern {pattern} for {}: {e}", game.name))
+                .ok()
+        })
+        .collect();
 
-            if file.is_dir() {
-                log::warn!("Found {} while backing up {}. Glob patterns should match files only.", file.display(), game.name);
-                continue;
+    let mut files = vec![];
+    let mut excluded = vec![];
+    let mut seen = HashSet::new();
+
+    for path in platforms.iter().filter_map(|platform| entry.files.for_platform(*platform)).flatten() {
+        // Windows ignores case, so saves in a prefix are matched in any casing from the placeholder's directory on
+        let base = Path::new(path).iter().next().map(|placeholder| expand(&placeholder.to_string_lossy())).unwrap_or_default();
+        let case_sensitive = !entry.ignore_case && !prefix.is_some_and(|prefix| Path::new(&base).starts_with(prefix));
+        let options = MatchOptions { case_sensitive, ..MatchOptions::new() };
+        let pattern = if case_sensitive {
+            expand(path)
+        } else {
+            dirs::ignore_case(&expand(path), Path::new(&base))
+        };
+        let found_paths = glob_with(&pattern, options).unwrap();
+
+        // Directories are captured with everything in them
+        for file in found_paths.flat_map(|found| {
+            let found = found.unwrap();
+
+            if found.is_dir() {
+                files_in(&found)
+            } else {
+                vec![found]
             }
-
-            if file.file_name().unwrap() == "steam_autocloud.vdf" {
+        }) {
+            // `**` patterns match directories as well as the files in them
+            if file.file_name().unwrap() == "steam_autocloud.vdf" || !seen.insert(file.clone()) {
                 continue;
             }
 
-            files.push(file);
+            if exclude.iter().any(|pattern| pattern.matches_path_with(&file, options)) {
+                excluded.push(file);
+            } else {
+                files.push(file);
+            }
         }
     }
 
-    if files.is_empty() {
-        return Ok(false);
-    }
-
-    create_dir_all(&backup_folder)?;
-
-    for file in files {
-        #[cfg(unix)]
-        let shrunk_file = shrink_path(file.as_path(), game.installation_dir.as_deref(), game.prefix.as_deref(), steam_id);
+    #[cfg(unix)]
+    files.extend(export_registry(game, entry));
 
-        #[cfg(windows)]
-        let shrunk_file = shrink_path(file.as_path(), game.installation_dir.as_deref(), steam_id);
+    (files, excluded)
+}
 
-        let shrunk_file_path = shrunk_file.to_string_lossy();
-        let should_backup = existing_manifest.as_ref().is_none_or(|manifest| {
-            manifest.files.iter().find(|m| m.path == shrunk_file_path).is_none_or(|existing| {
-                metadata(&file).unwrap().modified().unwrap() > existing.modified && existing.hash != hash_file(&file)
-            })
-        });
+/// Every file in a directory and its subdirectories, in order. Symlinked directories aren't followed so they can't loop.
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
 
-        if should_backup {
-            let file_metadata = process_file(&file, &backup_folder.join(file.file_name().unwrap()), game, steam_id);
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Failed to read {}: {e}", dir.display());
+            return files;
+        }
+    };
 
-            game_files.push(file_metadata);
-            changed = true;
-        } else {
-            let existing_metadata = existing_manifest.as_ref().unwrap().files.iter().find(|m| m.path == shrunk_file_path).unwrap();
+    for entry in entries.flatten() {
+        let path = entry.path();
 
-            game_files.push(existing_metadata.to_owned());
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            files.extend(files_in(&path));
+        } else if path.is_file() {
+            files.push(path);
         }
     }
 
-    if !changed {
-        return Ok(false);
-    }
+    files.sort();
+    files
+}
 
-    let game_metadata = GameInfo { name: game.name.clone(), files: game_files };
+/// Exports the game's registry keys from its prefix into the cache, so they're backed up and restored like its saves.
+/// The prefix itself is only read, keys are imported into it once a restore is applied.
+/// The export is only rewritten when the keys changed, to keep its modification time, and removed when none of them exist.
+#[cfg(unix)]
+pub(super) fn export_registry(game: &Game, entry: &GameDbEntry) -> Option<PathBuf> {
+    let prefix = game.prefix.as_deref().filter(|_| !entry.registry.is_empty())?;
+    let path = local_path(game, None, &format!("{{WineRegistry}}/{}.reg", utils::sanitize_game_name(&game.name)));
+    let exported = registry::export(prefix, &entry.registry).and_then(|export| match export {
+        Some(export) if read_to_string(&path).ok().as_ref() != Some(&export) => {
+            create_dir_all(path.parent().unwrap())?;
+            write(&path, export)
+        }
+        None if path.exists() => remove_file(&path),
+        _ => Ok(())
+    });
 
-    serde_yaml::to_writer(File::create(&manifest_path).unwrap(), &game_metadata).unwrap();
+    if let Err(e) = exported {
+        log::warn!("Failed to export the registry keys of {}: {e}", game.name);
+    }
 
-    Ok(true)
+    path.is_file().then_some(path)
 }
 
-fn process_file(file_path: &Path, dest: &Path, game: &Game, steam_id: Option<&str>) -> FileMetadata {
-    copy(file_path, dest).unwrap();
-
+fn process_file(file_path: &Path, game: &Game, steam_id: Option<&str>) -> FileMetadata {
     let file_metadata = metadata(file_path).unwrap();
 
     FileMetadata {
@@ -139,6 +429,139 @@ fn process_file(file_path: &Path, dest: &Path, game: &Game, steam_id: Option<&st
         #[cfg(windows)]
         path: shrink_path(file_path, game.installation_dir.as_deref(), steam_id).to_string_lossy().to_string(),
         hash: hash_file(file_path),
-        size: file_metadata.len()
+        size: file_metadata.len(),
+        compression: Compression::None,
+        stored_size: None,
+        encrypted: false,
+        mode: file_mode(&file_metadata)
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::operations::snapshot::latest_snapshot;
+    use std::fs::{remove_dir_all, write};
+
+    /// A game installed in `game` in a fresh directory for `test_name`, and backed up to `saves` next to it.
+    pub(in crate::operations) fn fixture(test_name: &str) -> (PathBuf, Config, Game) {
+        let root = std::env::temp_dir().join(format!("aletheia_{test_name}_test"));
+        let _ = remove_dir_all(&root);
+        let config = Config { save_dir: root.join("saves"), device_id: "test".into(), ..Config::default() };
+        let game = Game {
+            name: "Unit Test".into(),
+            installation_dir: Some(root.join("game")),
+            #[cfg(unix)]
+            prefix: None,
+            source: "Test".into(),
+            store_id: None
+        };
+
+        (root, config, game)
+    }
+
+    /// An entry saving the files matching `patterns` on every platform.
+    pub(in crate::operations) fn entry_matching(patterns: &str) -> GameDbEntry {
+        serde_yaml::from_str(&format!("files:\n  windows: {patterns}\n  linux: {patterns}\n  mac: {patterns}")).unwrap()
+    }
+
+    /// Writes each of `files` relative to `dir`, creating the directories they're in.
+    pub(in crate::operations) fn write_files(dir: &Path, files: &[(&str, &str)]) {
+        for (path, contents) in files {
+            let path = dir.join(path);
+
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, contents).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_excluded_files() {
+        let (root, mut config, game) = fixture("excluded_files");
+        let patterns = "['{GameRoot}/*', '{GameRoot}/*/*']";
+        let mut entry = entry_matching(patterns);
+
+        write_files(&root.join("game"), &[("a.dat", "backed up"), ("crash.log", "log"), ("cache/shader.bin", "cache")]);
+        backup_game(&game, &config, &entry, None, BackupOptions::default()).unwrap();
+
+        entry.files.exclude = Some(Box::new(entry_matching("['{GameRoot}/cache/*']").files));
+        config.game_exclude.insert("Unit Test".into(), vec!["*.log".into()]);
+        write(root.join("game/a.dat"), "progress").unwrap();
+
+        let plan = backup_game(&game, &config, &entry, None, BackupOptions::default()).unwrap();
+        let mut excluded: Vec<&Path> =
+            plan.files.iter().filter(|file| file.reason == Reason::Excluded).map(|file| file.path.as_path()).collect();
+        excluded.sort_unstable();
+
+        assert_eq!(excluded, [root.join("game/cache/shader.bin"), root.join("game/crash.log")]);
+
+        // Excluded files are left out of new snapshots without being recorded as deleted
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        let paths: Vec<&str> = snapshot.manifest.files.iter().map(|file| file.path.as_str()).collect();
+
+        assert_eq!(paths, ["{GameRoot}/a.dat"]);
+        assert!(snapshot.manifest.deleted.is_empty());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_directory_trees() {
+        let (root, config, game) = fixture("directory_trees");
+        let entry = entry_matching("['{GameRoot}/profiles', '{GameRoot}/worlds/**/*.dat']");
+        let files = ["profiles/default/settings.ini", "profiles/default/slots/1.sav", "worlds/a/b/world.dat", "worlds/level.dat"];
+
+        write_files(&root.join("game"), &files.map(|path| (path, "backed up")));
+        write_files(&root.join("game"), &[("worlds/a/notes.txt", "not a save")]);
+
+        let (found, _) = find_files(&game, &entry, &config);
+        let mut found: Vec<&Path> = found.iter().map(|path| path.strip_prefix(root.join("game")).unwrap()).collect();
+        found.sort_unstable();
+
+        assert_eq!(found, files.map(Path::new));
+
+        backup_game(&game, &config, &entry, None, BackupOptions::default()).unwrap();
+
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        let mut paths: Vec<&str> = snapshot.manifest.files.iter().map(|file| file.path.as_str()).collect();
+        paths.sort_unstable();
+
+        assert_eq!(paths, files.map(|path| format!("{{GameRoot}}/{path}")));
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_prefix_casing() {
+        let (root, config, game) = fixture("prefix_casing_backup");
+        let game = Game { installation_dir: None, prefix: Some(root.join("prefix")), ..game };
+        let entry: GameDbEntry = serde_yaml::from_str("files:\n  windows: ['{Documents}/My Games/Savegames/*.sav']").unwrap();
+
+        write_files(&root.join("prefix/drive_c/users/steamuser/Documents/my games/SaveGames"), &[("Slot1.sav", "backed up")]);
+        backup_game(&game, &config, &entry, None, BackupOptions::default()).unwrap();
+
+        // The casing on disk is recorded
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        assert_eq!(snapshot.manifest.files[0].path, "{Documents}/my games/SaveGames/Slot1.sav");
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let (root, config, game) = fixture("ignore_case");
+        let mut entry = entry_matching("['{GameRoot}/Saves/*.SAV']");
+
+        write_files(&root.join("game"), &[("saves/slot1.sav", "backed up")]);
+
+        // Their file systems ignore case anyway
+        #[cfg(not(any(windows, target_os = "macos")))]
+        assert!(find_files(&game, &entry, &config).0.is_empty());
+
+        entry.ignore_case = true;
+        assert_eq!(find_files(&game, &entry, &config).0, [root.join("game/saves/slot1.sav")]);
+
+        remove_dir_all(root).unwrap();
     }
 }
@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::codec::encoded_path;
+use super::encryption::Key;
+use super::snapshot::{self, read_snapshots};
+use crate::gamedb::{FileMetadata, Layout};
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, read_dir, remove_file, rename};
+use std::path::{Path, PathBuf};
+
+/// Shared by every game so identical files are only stored once.
+const BLOBS_DIR: &str = ".blobs";
+
+/// Compressed and encrypted blobs are kept apart from plain ones, as the same contents can be stored either way.
+pub fn blob_path(save_dir: &Path, file: &FileMetadata) -> PathBuf {
+    encoded_path(save_dir.join(BLOBS_DIR).join(&file.hash[..2]).join(&file.hash), file)
+}
+
+/// Stores a blob with `write` unless one with the same contents and encoding already exists, returning its size.
+pub fn store_blob<E: From<std::io::Error>>(
+    save_dir: &Path, file: &FileMetadata, write: impl FnOnce(&Path) -> Result<(), E>
+) -> Result<u64, E> {
+    let dest = blob_path(save_dir, file);
+
+    if !dest.exists() {
+        let partial = dest.with_extension("partial");
+
+        create_dir_all(dest.parent().unwrap())?;
+        write(&partial)?;
+        rename(&partial, &dest)?;
+    }
+
+    Ok(dest.metadata()?.len())
+}
+
+/// Counts how many snapshots reference each blob by file name, skipping the snapshots in `ignored`.
+/// Fails if any manifest can't be read, as the blobs it references would otherwise be treated as unused.
+fn reference_counts(save_dir: &Path, key: Option<&Key>, ignored: &HashSet<PathBuf>) -> snapshot::Result<HashMap<String, usize>> {
+    let mut references: HashMap<String, usize> = HashMap::new();
+
+    for entry in read_dir(save_dir)? {
+        let game_dir = entry?.path();
+
+        if !game_dir.is_dir() || game_dir.file_name().unwrap().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        for snapshot in read_snapshots(&game_dir, key, true)? {
+            if snapshot.manifest.layout != Layout::Blobs || ignored.contains(&snapshot.path) {
+                continue;
+            }
+
+            for file in &snapshot.manifest.files {
+                let blob = blob_path(Path::new(""), file);
+                *references.entry(blob.file_name().unwrap().to_string_lossy().to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    Ok(references)
+}
+
+/// Removes blobs no longer referenced by any snapshot, returning how many were (or with `dry_run`, would be) removed and their size.
+/// `ignored` lists snapshots that are about to be removed, which is used to preview a prune.
+pub fn collect_garbage(
+    save_dir: &Path, key: Option<&Key>, ignored: &HashSet<PathBuf>, dry_run: bool
+) -> snapshot::Result<(usize, u64)> {
+    let blobs_dir = save_dir.join(BLOBS_DIR);
+
+    if !blobs_dir.exists() {
+        return Ok((0, 0));
+    }
+
+    let references = reference_counts(save_dir, key, ignored)?;
+    let mut removed = 0;
+    let mut freed = 0;
+
+    for bucket in read_dir(&blobs_dir)? {
+        for blob in read_dir(bucket?.path())? {
+            let blob = blob?.path();
+            let name = blob.file_name().unwrap().to_string_lossy();
+
+            if references.get(name.as_ref()).is_some_and(|count| *count > 0) {
+                continue;
+            }
+
+            freed += blob.metadata()?.len();
+            removed += 1;
+
+            if !dry_run {
+                remove_file(&blob)?;
+            }
+        }
+    }
+
+    Ok((removed, freed))
+}
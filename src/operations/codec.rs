@@ -0,0 +1,120 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::encryption::{Error, Key, Result};
+use crate::file::hash_reader;
+use crate::gamedb::{Compression, FileMetadata};
+use std::fs::{File, read};
+use std::io::{BufReader, BufWriter, Cursor, Read, Write, copy};
+use std::path::{Path, PathBuf};
+
+/// Where a file is stored once encoded as described by `file`.
+pub fn encoded_path(path: PathBuf, file: &FileMetadata) -> PathBuf {
+    let extensions = [file.compression.extension(), file.encrypted.then_some("enc")];
+
+    if extensions.iter().all(Option::is_none) {
+        return path;
+    }
+
+    let mut path = path.into_os_string();
+
+    for extension in extensions.into_iter().flatten() {
+        path.push(".");
+        path.push(extension);
+    }
+
+    path.into()
+}
+
+/// Writes `source` to `dest` using `compression`, encrypting it when a key is given, and returns the size of the written file.
+/// Files are compressed before being encrypted, as encrypted data doesn't compress.
+pub fn encode_file(source: &Path, dest: &Path, compression: Compression, key: Option<&Key>) -> Result<u64> {
+    let mut reader = BufReader::new(File::open(source)?);
+    let mut writer = BufWriter::new(File::create(dest)?);
+
+    if let Some(key) = key {
+        let mut encoded = vec![];
+        compress(&mut reader, &mut encoded, compression)?;
+        writer.write_all(&key.encrypt(&encoded))?;
+    } else {
+        compress(&mut reader, &mut writer, compression)?;
+    }
+
+    writer.into_inner().map_err(std::io::IntoInnerError::into_error)?.sync_all()?;
+
+    Ok(dest.metadata()?.len())
+}
+
+fn compress(reader: &mut impl Read, writer: &mut impl Write, compression: Compression) -> std::io::Result<()> {
+    match compression {
+        Compression::None => copy(reader, writer).map(|_| ()),
+        Compression::Zstd => zstd::stream::copy_encode(reader, writer, zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+/// Reads the original contents of a file stored at `path`, failing if it's encrypted and doesn't authenticate.
+pub fn decode_reader(path: &Path, file: &FileMetadata, key: Option<&Key>) -> Result<Box<dyn Read>> {
+    let reader: Box<dyn Read> = if file.encrypted {
+        Box::new(Cursor::new(key.ok_or(Error::MissingKey)?.decrypt(&read(path)?)?))
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+
+    Ok(match file.compression {
+        Compression::None => reader,
+        Compression::Zstd => Box::new(zstd::stream::Decoder::new(reader)?)
+    })
+}
+
+/// Hashes the original contents of a stored file, so it can be compared to [`FileMetadata::hash`].
+pub fn hash_stored(path: &Path, file: &FileMetadata, key: Option<&Key>) -> Result<String> {
+    Ok(hash_reader(decode_reader(path, file, key)?)?)
+}
+
+/// Writes the original contents of a stored file to `dest`, which is synced to disk before returning.
+pub fn decode_file(path: &Path, file: &FileMetadata, key: Option<&Key>, dest: &Path) -> Result<()> {
+    let mut reader = decode_reader(path, file, key)?;
+    let mut writer = BufWriter::new(File::create(dest)?);
+
+    copy(&mut reader, &mut writer)?;
+    Ok(writer.into_inner().map_err(std::io::IntoInnerError::into_error)?.sync_all()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::hash_file;
+    use std::fs::{create_dir_all, read, remove_dir_all, write};
+
+    #[test]
+    fn test_zstd_round_trip() {
+        let dir = std::env::temp_dir().join("aletheia_codec_test");
+        let original = dir.join("save.dat");
+        let restored = dir.join("restored.dat");
+        let contents = "save data ".repeat(1000);
+        let file = FileMetadata {
+            hash: String::new(),
+            modified: std::time::SystemTime::UNIX_EPOCH,
+            path: String::new(),
+            size: 0,
+            compression: Compression::Zstd,
+            stored_size: None,
+            encrypted: false,
+            mode: None
+        };
+        let stored = encoded_path(dir.join("stored"), &file);
+
+        create_dir_all(&dir).unwrap();
+        write(&original, &contents).unwrap();
+
+        let stored_size = encode_file(&original, &stored, Compression::Zstd, None).unwrap();
+        assert_eq!(stored.extension().unwrap(), "zst");
+        assert!(stored_size < contents.len() as u64);
+        assert_eq!(hash_stored(&stored, &file, None).unwrap(), hash_file(&original));
+
+        decode_file(&stored, &file, None, &restored).unwrap();
+        assert_eq!(read(&restored).unwrap(), contents.as_bytes());
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
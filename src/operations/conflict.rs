@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::backup::{self, BackupOptions, backup_game};
+use super::encryption::Key;
+use super::restore::{self, RestoreOptions, restore_game};
+use super::snapshot::{self, SnapshotSelector, list_snapshots, select_snapshot};
+use crate::config::Config;
+use crate::gamedb::GameDbEntry;
+use crate::scanner::Game;
+use crate::utils;
+use std::fmt;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to back up local saves: {0}")]
+    Backup(#[from] backup::Error),
+    #[error("Failed to restore saves from the other device: {0}")]
+    Restore(#[from] restore::Error),
+    #[error("Failed to read snapshots: {0}")]
+    Snapshot(#[from] snapshot::Error),
+    #[error("Snapshot {0} no longer exists")]
+    MissingSnapshot(String)
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// How to settle saves that changed both on this device and on another one since they were last synced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Back up the saves on this device on top of the other device's.
+    Local,
+    /// Restore the other device's saves over the ones on this device.
+    Remote,
+    /// Keep the saves on this device as a conflict copy, then restore the other device's.
+    Both
+}
+
+impl Resolution {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input {
+            "keep-local" => Some(Self::Local),
+            "keep-remote" => Some(Self::Remote),
+            "keep-both" => Some(Self::Both),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Conflict {
+    pub game: String,
+    /// The other device's snapshot.
+    pub snapshot: String,
+    /// The other device's hostname, when it was recorded.
+    pub hostname: Option<String>
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}'s saves changed both here and on {} (snapshot {}) since they were last synced",
+            self.game,
+            self.hostname.as_deref().unwrap_or("another device"),
+            self.snapshot
+        )
+    }
+}
+
+pub fn resolve_conflict(
+    conflict: &Conflict, game: &Game, entry: &GameDbEntry, config: &Config, key: Option<&Key>, resolution: Resolution
+) -> Result<()> {
+    if resolution != Resolution::Remote {
+        backup_game(game, config, entry, key, BackupOptions { resolution: Some(resolution), dry_run: false })?;
+    }
+
+    if resolution != Resolution::Local {
+        let game_dir = config.save_dir.join(utils::sanitize_game_name(&game.name).as_ref());
+        let selector = SnapshotSelector::Id(conflict.snapshot.clone());
+        let snapshot = select_snapshot(list_snapshots(&game_dir, key)?, &selector)
+            .ok_or_else(|| Error::MissingSnapshot(conflict.snapshot.clone()))?;
+
+        // Choosing the other device's saves is choosing to replace the ones here
+        let options = RestoreOptions { resolution: Some(resolution), force: true, entry: Some(entry), ..RestoreOptions::default() };
+
+        restore_game(&snapshot, std::slice::from_ref(game), config, key, options)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::config::{Config, KeySource};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use std::fs::{File, read, remove_file, rename};
+use std::path::Path;
+
+pub const PASSPHRASE_VAR: &str = "ALETHEIA_PASSPHRASE";
+
+/// Stored in the save directory so every device syncing it derives the same key.
+const PARAMETERS_FILE: &str = ".encryption.yaml";
+/// Parameters of a key rotation that hasn't finished yet.
+const PENDING_PARAMETERS_FILE: &str = ".encryption.pending.yaml";
+const MAGIC: &[u8] = b"ALETHEIA-ENC1";
+const NONCE_SIZE: usize = 24;
+/// Encrypted into the parameters file to tell a wrong key apart from tampered backups.
+const CHECK: &[u8] = b"aletheia";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to authenticate, the backup was either tampered with or encrypted with a different key")]
+    Authentication,
+    #[error("Backups are encrypted, but no key was provided")]
+    MissingKey,
+    #[error("No passphrase provided, set {PASSPHRASE_VAR} to the passphrase backups are encrypted with")]
+    MissingPassphrase,
+    #[error("Incorrect passphrase or key file")]
+    IncorrectKey,
+    #[error("Failed to derive key")]
+    KeyDerivation,
+    #[error("Malformed encryption parameters")]
+    MalformedParameters,
+    #[error("Another key rotation is in progress, rerun it with the same new key to finish it")]
+    RotationInProgress
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Parameters {
+    salt: String,
+    check: String
+}
+
+pub struct Key {
+    cipher: XChaCha20Poly1305,
+    /// Also tried when decrypting, so a rotation can be resumed after some files were already re-encrypted.
+    previous: Option<XChaCha20Poly1305>
+}
+
+impl Key {
+    fn derive(secret: &[u8], salt: &[u8]) -> Result<Self> {
+        let mut key = [0; 32];
+
+        Argon2::default().hash_password_into(secret, salt, &mut key).map_err(|_| Error::KeyDerivation)?;
+
+        Ok(Self { cipher: XChaCha20Poly1305::new(&key.into()), previous: None })
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher.encrypt(&nonce, data).expect("Encryption only fails for oversized buffers");
+
+        [MAGIC, nonce.as_slice(), &ciphertext].concat()
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let data = data.strip_prefix(MAGIC).filter(|data| data.len() >= NONCE_SIZE).ok_or(Error::Authentication)?;
+        let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+        let nonce = XNonce::from_slice(nonce);
+
+        self.cipher
+            .decrypt(nonce, ciphertext)
+            .or_else(|_| self.previous.as_ref().ok_or(chacha20poly1305::Error)?.decrypt(nonce, ciphertext))
+            .map_err(|_| Error::Authentication)
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Decrypts `data` if it's encrypted, otherwise returns it as is.
+pub fn open(data: Vec<u8>, key: Option<&Key>) -> Result<Vec<u8>> {
+    if is_encrypted(&data) {
+        key.ok_or(Error::MissingKey)?.decrypt(&data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Derives the key backups are encrypted with, or returns `None` if encryption is disabled.
+/// Passphrases are read from [`PASSPHRASE_VAR`] unless `passphrase` is given.
+pub fn load_key(config: &Config, passphrase: Option<String>) -> Result<Option<Key>> {
+    let Some(source) = &config.encryption else {
+        return Ok(None);
+    };
+
+    let secret = read_secret(source, passphrase)?;
+    let params_path = config.save_dir.join(PARAMETERS_FILE);
+
+    if params_path.exists() {
+        return unlock(&params_path, &secret).map(Some);
+    }
+
+    let (key, params) = create_key(&secret)?;
+    write_parameters(&params_path, &params)?;
+
+    Ok(Some(key))
+}
+
+pub fn read_secret(source: &KeySource, passphrase: Option<String>) -> Result<Vec<u8>> {
+    match source {
+        KeySource::Passphrase => passphrase
+            .or_else(|| std::env::var(PASSPHRASE_VAR).ok())
+            .filter(|passphrase| !passphrase.is_empty())
+            .map(String::into_bytes)
+            .ok_or(Error::MissingPassphrase),
+        KeySource::KeyFile(path) => Ok(read(path)?)
+    }
+}
+
+/// Derives the key a rotation encrypts with, reusing the parameters of an interrupted rotation so it can be finished.
+/// The returned key can still decrypt files encrypted with `old`.
+pub(super) fn begin_rotation(save_dir: &Path, secret: &[u8], old: Option<&Key>) -> Result<Key> {
+    let pending_path = save_dir.join(PENDING_PARAMETERS_FILE);
+
+    let mut key = if pending_path.exists() {
+        unlock(&pending_path, secret).map_err(|e| match e {
+            Error::IncorrectKey => Error::RotationInProgress,
+            e => e
+        })?
+    } else {
+        let (key, params) = create_key(secret)?;
+        write_parameters(&pending_path, &params)?;
+        key
+    };
+
+    key.previous = old.map(|old| old.cipher.clone());
+
+    Ok(key)
+}
+
+/// Makes the key of a finished rotation the current one, or removes the parameters when encryption was disabled.
+pub(super) fn finish_rotation(save_dir: &Path, encrypted: bool) -> Result<()> {
+    let params_path = save_dir.join(PARAMETERS_FILE);
+
+    if encrypted {
+        rename(save_dir.join(PENDING_PARAMETERS_FILE), params_path)?;
+    } else if params_path.exists() {
+        remove_file(params_path)?;
+    }
+
+    Ok(())
+}
+
+fn create_key(secret: &[u8]) -> Result<(Key, Parameters)> {
+    let mut salt = [0; 16];
+    OsRng.fill_bytes(&mut salt);
+
+    let key = Key::derive(secret, &salt)?;
+    let check = key.encrypt(CHECK);
+
+    Ok((key, Parameters { salt: hex::encode(salt), check: hex::encode(check) }))
+}
+
+fn unlock(params_path: &Path, secret: &[u8]) -> Result<Key> {
+    let params: Parameters = serde_yaml::from_reader(File::open(params_path)?).map_err(|_| Error::MalformedParameters)?;
+    let salt = hex::decode(&params.salt).map_err(|_| Error::MalformedParameters)?;
+    let check = hex::decode(&params.check).map_err(|_| Error::MalformedParameters)?;
+    let key = Key::derive(secret, &salt)?;
+
+    match key.decrypt(&check) {
+        Ok(check) if check == CHECK => Ok(key),
+        _ => Err(Error::IncorrectKey)
+    }
+}
+
+fn write_parameters(path: &Path, params: &Parameters) -> Result<()> {
+    serde_yaml::to_writer(File::create(path)?, params).map_err(|_| Error::MalformedParameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encryption() {
+        let (key, params) = create_key(b"correct horse").unwrap();
+        let (other_key, _) = create_key(b"correct horse").unwrap();
+        let encrypted = key.encrypt(b"save data");
+
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(key.decrypt(&encrypted).unwrap(), b"save data");
+        assert!(matches!(other_key.decrypt(&encrypted), Err(Error::Authentication)));
+
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(key.decrypt(&tampered), Err(Error::Authentication)));
+        assert!(matches!(key.decrypt(b"save data"), Err(Error::Authentication)));
+        assert!(matches!(open(encrypted, None), Err(Error::MissingKey)));
+
+        let salt = hex::decode(params.salt).unwrap();
+        let check = hex::decode(params.check).unwrap();
+        assert_eq!(Key::derive(b"correct horse", &salt).unwrap().decrypt(&check).unwrap(), CHECK);
+        assert!(Key::derive(b"wrong horse", &salt).unwrap().decrypt(&check).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::snapshot::Snapshot;
+use crate::config::Config;
+use crate::file::hash_file;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read, read_dir, rename, write};
+use std::path::{Path, PathBuf};
+
+const DEVICES_DIR: &str = ".devices";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Malformed device file")]
+    MalformedRecord
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// What a device last synced with. It's kept in `save_dir` so it stays with the backups it refers to.
+#[derive(Default, Deserialize, Serialize)]
+struct DeviceRecord {
+    /// The snapshot each game's saves were last backed up to or restored from.
+    synced: BTreeMap<String, String>
+}
+
+/// How the saves on this device relate to a snapshot.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum History {
+    /// The snapshot can replace the saves, or the other way around, without losing changes from another device.
+    Linear,
+    /// Another device made the snapshot, and the saves here haven't changed since they were last synced.
+    Behind,
+    /// Both the saves here and the snapshot from another device changed since they were last synced.
+    Diverged
+}
+
+fn record_path(config: &Config) -> PathBuf {
+    config.save_dir.join(DEVICES_DIR).join(format!("{}.yaml", config.device_id))
+}
+
+fn read_record(config: &Config) -> Result<DeviceRecord> {
+    let path = record_path(config);
+
+    if !path.exists() {
+        return Ok(DeviceRecord::default());
+    }
+
+    serde_yaml::from_slice(&read(path)?).map_err(|_| Error::MalformedRecord)
+}
+
+/// The snapshot a game's saves were last synced with on this device.
+pub fn synced_snapshot(config: &Config, game_name: &str) -> Result<Option<String>> {
+    Ok(read_record(config)?.synced.remove(game_name))
+}
+
+/// The snapshots every device sharing `save_dir` last synced a game's saves with, which they're compared against later.
+pub(super) fn synced_by_devices(save_dir: &Path, game_name: &str) -> Result<Vec<String>> {
+    let devices_dir = save_dir.join(DEVICES_DIR);
+
+    if !devices_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut synced = vec![];
+
+    for entry in read_dir(devices_dir)? {
+        let path = entry?.path();
+
+        if path.extension().is_some_and(|extension| extension == "yaml") {
+            let record: DeviceRecord = serde_yaml::from_slice(&read(path)?).map_err(|_| Error::MalformedRecord)?;
+            synced.extend(record.synced.get(game_name).cloned());
+        }
+    }
+
+    Ok(synced)
+}
+
+/// Records the snapshot a game's saves on this device match, or that they were never synced when `None`.
+pub fn mark_synced(config: &Config, game_name: &str, snapshot_id: Option<&str>) -> Result<()> {
+    let mut record = read_record(config)?;
+
+    if record.synced.get(game_name).map(String::as_str) == snapshot_id {
+        return Ok(());
+    }
+
+    match snapshot_id {
+        Some(snapshot_id) => record.synced.insert(game_name.to_owned(), snapshot_id.to_owned()),
+        None => record.synced.remove(game_name)
+    };
+
+    let path = record_path(config);
+    let partial = path.with_extension("partial");
+
+    create_dir_all(path.parent().unwrap())?;
+    write(&partial, serde_yaml::to_string(&record).map_err(|_| Error::MalformedRecord)?)?;
+
+    Ok(rename(partial, path)?)
+}
+
+pub fn hostname() -> Option<String> {
+    whoami::fallible::hostname().ok()
+}
+
+/// Compares the saves on this device with `snapshot`, given the snapshot they were last synced with.
+/// `unchanged_since` tells whether the saves still match a snapshot, and `has_saves` whether there are any to lose.
+pub(super) fn compare(
+    snapshots: &[Snapshot], synced: Option<&str>, snapshot: &Snapshot, device_id: &str, has_saves: bool,
+    unchanged_since: impl Fn(&Snapshot) -> bool
+) -> History {
+    // Snapshots from before devices were recorded are treated as this device's own
+    let from_other_device = snapshot.manifest.device.as_ref().is_some_and(|device| device != device_id);
+
+    // Older snapshots this device already synced past, such as one being rolled back to, aren't remote changes
+    if !from_other_device || synced.is_some_and(|synced| is_ancestor(snapshots, &snapshot.id, synced)) {
+        return History::Linear;
+    }
+
+    if !has_saves || unchanged_since(snapshot) {
+        return History::Linear;
+    }
+
+    // When the synced snapshot was pruned, the newest one before this snapshot stands in for it
+    let base = synced.and_then(|synced| {
+        snapshots
+            .iter()
+            .find(|s| s.id == synced)
+            .or_else(|| snapshots.iter().rev().find(|s| s.id != snapshot.id && s.created() <= snapshot.created()))
+    });
+
+    match base {
+        Some(base) if unchanged_since(base) => History::Behind,
+        _ => History::Diverged
+    }
+}
+
+/// Whether every file of `snapshot` is on disk unchanged and the ones it deleted are gone,
+/// with `locate` mapping its shrunk path to where it would be.
+pub(super) fn matches_local(snapshot: &Snapshot, locate: impl Fn(&str) -> Option<PathBuf>) -> bool {
+    let manifest = &snapshot.manifest;
+
+    manifest.files.iter().all(|file| locate(&file.path).is_some_and(|path| path.is_file() && hash_file(&path) == file.hash))
+        && manifest.deleted.iter().all(|path| locate(path).is_none_or(|path| !path.is_file()))
+}
+
+/// Whether `ancestor` is `id` or one of the snapshots it was made on top of.
+fn is_ancestor(snapshots: &[Snapshot], ancestor: &str, id: &str) -> bool {
+    let mut current = Some(id);
+
+    // Bounded in case parents were edited into a cycle
+    for _ in 0..=snapshots.len() {
+        let Some(id) = current else {
+            break;
+        };
+
+        if id == ancestor {
+            return true;
+        }
+
+        current = snapshots.iter().find(|s| s.id == id).and_then(|s| s.manifest.parent.as_deref());
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamedb::{GameInfo, Layout};
+
+    fn snapshot(id: &str, device: &str, parent: Option<&str>) -> Snapshot {
+        Snapshot {
+            id: id.to_owned(),
+            path: PathBuf::from(id),
+            manifest: GameInfo {
+                name: "Unit Test".into(),
+                created: None,
+                pinned: false,
+                tag: None,
+                layout: Layout::Tree,
+                device: Some(device.to_owned()),
+                hostname: None,
+                parent: parent.map(ToOwned::to_owned),
+                conflict: false,
+                platform: None,
+                files: vec![],
+                deleted: vec![],
+                restored_to: None
+            },
+            encrypted: false
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let snapshots = vec![snapshot("1", "a", None), snapshot("2", "b", Some("1")), snapshot("3", "a", Some("2"))];
+        let unchanged_since = |id: &'static str| move |snapshot: &Snapshot| snapshot.id == id;
+
+        // Backups made here never conflict
+        assert_eq!(compare(&snapshots, Some("1"), &snapshots[2], "a", true, unchanged_since("1")), History::Linear);
+        // Device b synced 2, and its saves are unchanged since
+        assert_eq!(compare(&snapshots, Some("2"), &snapshots[2], "b", true, unchanged_since("2")), History::Behind);
+        // Device b changed its saves since syncing 2
+        assert_eq!(compare(&snapshots, Some("2"), &snapshots[2], "b", true, |_| false), History::Diverged);
+        // Rolling back to a snapshot device b already synced past
+        assert_eq!(compare(&snapshots, Some("2"), &snapshots[0], "b", true, |_| false), History::Linear);
+        // A new device without saves
+        assert_eq!(compare(&snapshots, None, &snapshots[2], "c", false, |_| false), History::Linear);
+        assert_eq!(compare(&snapshots, None, &snapshots[2], "c", true, |_| false), History::Diverged);
+        assert_eq!(compare(&snapshots, None, &snapshots[2], "c", true, unchanged_since("3")), History::Linear);
+
+        // Device b synced a snapshot that was pruned since, and its saves match the newest one left before 3
+        assert_eq!(compare(&snapshots, Some("pruned"), &snapshots[2], "b", true, unchanged_since("2")), History::Behind);
+        assert_eq!(compare(&snapshots, Some("pruned"), &snapshots[2], "b", true, |_| false), History::Diverged);
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::utils;
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// What a backup or restore did to each file, or would do in a dry run.
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    pub game: String,
+    /// The snapshot restored from or backed up to, which isn't known for a backup until it's made.
+    pub snapshot: Option<String>,
+    pub files: Vec<PlannedFile>
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedFile {
+    /// Where the file is on this device.
+    pub path: PathBuf,
+    pub action: Action,
+    pub size: u64,
+    pub reason: Reason
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Stored in the new snapshot.
+    Copy,
+    /// Left as it is.
+    Skip,
+    /// Restored where there's no file yet.
+    Create,
+    /// Restored over the file that's there.
+    Overwrite,
+    /// Removed as it was deleted when the snapshot was made, or recorded as deleted in a new one.
+    Remove
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    New,
+    Changed,
+    Unchanged,
+    /// Stored again, as the previous copy isn't encrypted the way new ones are.
+    EncryptionChanged,
+    Missing,
+    Differs,
+    Deleted,
+    DeletedLocally,
+    /// Matches the game's paths but isn't in the snapshot, only removed when asked to.
+    NotInSnapshot,
+    /// Another device's saves are kept, either because they're newer or to settle a conflict.
+    Superseded,
+    /// The saves here are kept to settle a conflict.
+    KeptLocal,
+    /// Backed up on another platform, with no equivalent location on this one.
+    Unmapped,
+    /// Matches an exclude pattern from the game database or the config.
+    Excluded
+}
+
+impl Plan {
+    pub fn new(game: &str, snapshot: Option<String>) -> Self {
+        Self { game: game.to_owned(), snapshot, files: vec![] }
+    }
+
+    pub fn add(&mut self, path: PathBuf, action: Action, size: u64, reason: Reason) {
+        self.files.push(PlannedFile { path, action, size, reason });
+    }
+
+    /// Whether anything is copied, restored or removed.
+    pub fn changed(&self) -> bool {
+        self.files.iter().any(|file| file.action != Action::Skip)
+    }
+
+    /// Leaves every file as it is.
+    pub(super) fn skip_all(&mut self, reason: Reason) {
+        for file in &mut self.files {
+            file.action = Action::Skip;
+            file.reason = reason;
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.snapshot {
+            Some(snapshot) => writeln!(f, "{} (snapshot {snapshot}):", self.game)?,
+            None => writeln!(f, "{}:", self.game)?
+        }
+
+        if self.files.is_empty() {
+            return writeln!(f, "  No files found");
+        }
+
+        for file in &self.files {
+            writeln!(f, "  {:<9} {} ({}, {})", file.action, file.path.display(), utils::format_size(file.size), file.reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Self::Copy => "copy",
+            Self::Skip => "skip",
+            Self::Create => "create",
+            Self::Overwrite => "overwrite",
+            Self::Remove => "remove"
+        };
+
+        f.pad(action)
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::New => "not backed up yet",
+            Self::Changed => "changed since the last backup",
+            Self::Unchanged => "unchanged",
+            Self::EncryptionChanged => "stored again to match encryption",
+            Self::Missing => "missing here",
+            Self::Differs => "differs from the snapshot",
+            Self::Deleted => "deleted in the snapshot",
+            Self::DeletedLocally => "deleted since the last backup",
+            Self::NotInSnapshot => "not in the snapshot",
+            Self::Superseded => "another device's saves are kept",
+            Self::KeptLocal => "the saves here are kept",
+            Self::Unmapped => "no equivalent location on this platform",
+            Self::Excluded => "excluded"
+        })
+    }
+}
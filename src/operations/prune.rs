@@ -0,0 +1,240 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::encryption::Key;
+use super::history;
+use super::snapshot::{self, Snapshot, list_snapshots};
+use crate::config::RetentionPolicy;
+use crate::dirs::get_size;
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate};
+use std::collections::HashSet;
+use std::fs::remove_dir_all;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to remove snapshot: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to read snapshots: {0}")]
+    Snapshot(#[from] snapshot::Error),
+    #[error("Failed to read sync history: {0}")]
+    History(#[from] history::Error)
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+pub struct PrunedSnapshot {
+    pub id: String,
+    pub path: PathBuf,
+    pub size: u64
+}
+
+/// Removes every snapshot of a game that `policy` doesn't keep, returning what was (or with `dry_run`, would be) removed.
+/// Snapshots a device last synced with are kept, as that device's saves are compared against them.
+pub fn prune_game(game_dir: &Path, key: Option<&Key>, policy: &RetentionPolicy, dry_run: bool) -> Result<Vec<PrunedSnapshot>> {
+    let snapshots = list_snapshots(game_dir, key)?;
+    let mut pruned = vec![];
+
+    let Some(newest) = snapshots.last() else {
+        return Ok(pruned);
+    };
+
+    // Games are backed up to <save_dir>/<game>
+    let synced = history::synced_by_devices(game_dir.parent().unwrap(), &newest.manifest.name)?;
+
+    for snapshot in expired_snapshots(&snapshots, policy, SystemTime::now(), &synced) {
+        let size = get_size(&snapshot.path);
+
+        if !dry_run {
+            remove_dir_all(&snapshot.path)?;
+        }
+
+        pruned.push(PrunedSnapshot { id: snapshot.id.clone(), path: snapshot.path.clone(), size });
+    }
+
+    Ok(pruned)
+}
+
+/// Expects snapshots oldest first, as returned by [`list_snapshots`].
+/// The newest snapshot that isn't a conflict copy is always kept so a policy can never remove every backup of a game.
+/// So are the ones in `synced` and their parents back to one that's kept anyway, so older snapshots are still known to be their ancestors.
+fn expired_snapshots<'a>(
+    snapshots: &'a [Snapshot], policy: &RetentionPolicy, now: SystemTime, synced: &[String]
+) -> Vec<&'a Snapshot> {
+    if policy.is_empty() {
+        return vec![];
+    }
+
+    let now = DateTime::<Local>::from(now);
+    let newest_first: Vec<(usize, DateTime<Local>)> =
+        snapshots.iter().enumerate().rev().map(|(index, snapshot)| (index, DateTime::from(snapshot.created()))).collect();
+    let mut keep: HashSet<usize> = HashSet::new();
+
+    keep.extend(newest_first.iter().find(|(index, _)| !snapshots[*index].manifest.conflict).map(|(index, _)| *index));
+
+    if let Some(count) = policy.last {
+        keep.extend(newest_first.iter().take(count).map(|(index, _)| *index));
+    }
+
+    if let Some(days) = policy.within_days {
+        let cutoff = now - Days::new(days.into());
+        keep.extend(newest_first.iter().filter(|(_, created)| *created >= cutoff).map(|(index, _)| *index));
+    }
+
+    let mut keep_per_period =
+        |periods: Option<u32>, cutoff: fn(DateTime<Local>, u32) -> DateTime<Local>, period: fn(NaiveDate) -> (i32, u32)| {
+            if let Some(periods) = periods {
+                let cutoff = cutoff(now, periods);
+                let mut seen = HashSet::new();
+
+                for (index, created) in newest_first.iter().filter(|(_, created)| *created >= cutoff) {
+                    if seen.insert(period(created.date_naive())) {
+                        keep.insert(*index);
+                    }
+                }
+            }
+        };
+
+    keep_per_period(policy.daily, |now, days| now - Days::new(days.into()), |date| (date.year(), date.ordinal()));
+    keep_per_period(
+        policy.weekly,
+        |now, weeks| now - Days::new(u64::from(weeks) * 7),
+        |date| {
+            let week = date.iso_week();
+            (week.year(), week.week())
+        }
+    );
+    keep_per_period(policy.monthly, |now, months| now - Months::new(months), |date| (date.year(), date.month()));
+
+    keep.extend(snapshots.iter().enumerate().filter(|(_, snapshot)| snapshot.is_protected()).map(|(index, _)| index));
+
+    let kept = keep.clone();
+    let position = |id: &str| snapshots.iter().position(|snapshot| snapshot.id == id);
+
+    for id in synced {
+        let mut current = position(id);
+
+        // Bounded in case parents were edited into a cycle
+        for _ in 0..snapshots.len() {
+            let Some(index) = current.filter(|index| !kept.contains(index)) else {
+                break;
+            };
+
+            keep.insert(index);
+            current = snapshots[index].manifest.parent.as_deref().and_then(position);
+        }
+    }
+
+    snapshots.iter().enumerate().filter(|(index, _)| !keep.contains(index)).map(|(_, s)| s).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamedb::{GameInfo, Layout};
+    use std::time::Duration;
+
+    const DAY: u64 = 86400;
+
+    fn snapshot(id: &str, created: SystemTime) -> Snapshot {
+        Snapshot {
+            id: id.to_owned(),
+            path: PathBuf::from(id),
+            manifest: GameInfo {
+                name: "Unit Test".into(),
+                created: Some(created),
+                pinned: false,
+                tag: None,
+                layout: Layout::Tree,
+                device: None,
+                hostname: None,
+                parent: None,
+                conflict: false,
+                platform: None,
+                files: vec![],
+                deleted: vec![],
+                restored_to: None
+            },
+            encrypted: false
+        }
+    }
+
+    fn expired_ids(snapshots: &[Snapshot], policy: &RetentionPolicy, now: SystemTime) -> Vec<String> {
+        expired_snapshots(snapshots, policy, now, &[]).into_iter().map(|s| s.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_retention() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(DAY * 20000 + DAY / 2);
+        let snapshots: Vec<Snapshot> =
+            (0..10).rev().map(|days_ago| snapshot(&format!("{days_ago}d"), now - Duration::from_secs(DAY * days_ago))).collect();
+
+        assert!(expired_ids(&snapshots, &RetentionPolicy::default(), now).is_empty());
+
+        let last = RetentionPolicy { last: Some(3), ..Default::default() };
+        assert_eq!(expired_ids(&snapshots, &last, now), ["9d", "8d", "7d", "6d", "5d", "4d", "3d"]);
+
+        let keep_within = RetentionPolicy { within_days: Some(5), ..Default::default() };
+        assert_eq!(expired_ids(&snapshots, &keep_within, now), ["9d", "8d", "7d", "6d"]);
+
+        let weekly = RetentionPolicy { weekly: Some(52), ..Default::default() };
+        assert!(expired_ids(&snapshots, &weekly, now).len() >= 7);
+
+        let keep_nothing_recent = RetentionPolicy { within_days: Some(0), ..Default::default() };
+        assert_eq!(expired_ids(&snapshots, &keep_nothing_recent, now).len(), 9);
+    }
+
+    #[test]
+    fn test_retention_keeps_protected_snapshots() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(DAY * 20000);
+        let mut snapshots = vec![
+            snapshot("pinned", now - Duration::from_secs(DAY * 3)),
+            snapshot("tagged", now - Duration::from_secs(DAY * 2)),
+            snapshot("old", now - Duration::from_secs(DAY)),
+            snapshot("new", now),
+        ];
+
+        snapshots[0].manifest.pinned = true;
+        snapshots[1].manifest.tag = Some("before-boss".into());
+
+        let policy = RetentionPolicy { last: Some(1), ..Default::default() };
+        assert_eq!(expired_ids(&snapshots, &policy, now), ["old"]);
+    }
+
+    #[test]
+    fn test_retention_keeps_one_snapshot_per_day() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(DAY * 20000 + DAY / 2);
+        let snapshots = vec![
+            snapshot("morning", now - Duration::from_secs(DAY + 3600 * 2)),
+            snapshot("evening", now - Duration::from_secs(DAY - 3600 * 2)),
+            snapshot("today", now),
+        ];
+
+        let policy = RetentionPolicy { daily: Some(7), ..Default::default() };
+        assert_eq!(expired_ids(&snapshots, &policy, now), ["morning"]);
+    }
+
+    #[test]
+    fn test_retention_keeps_synced_snapshots() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(DAY * 20000);
+        let mut snapshots: Vec<Snapshot> =
+            (0..6).rev().map(|days_ago| snapshot(&format!("{days_ago}d"), now - Duration::from_secs(DAY * days_ago))).collect();
+
+        for index in 1..snapshots.len() {
+            snapshots[index].manifest.parent = Some(snapshots[index - 1].id.clone());
+        }
+
+        snapshots[1].manifest.pinned = true;
+
+        // Another device last synced 2d, whose parents are kept back to the pinned 4d
+        let policy = RetentionPolicy { last: Some(1), ..Default::default() };
+        let expired = |synced: &str| -> Vec<String> {
+            expired_snapshots(&snapshots, &policy, now, &[synced.into()]).into_iter().map(|s| s.id.clone()).collect()
+        };
+
+        assert_eq!(expired("2d"), ["5d", "1d"]);
+        assert_eq!(expired("4d"), ["5d", "3d", "2d", "1d"]);
+        assert_eq!(expired("pruned"), ["5d", "3d", "2d", "1d"]);
+    }
+}
@@ -0,0 +1,297 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::fs::read_to_string;
+use std::io;
+use std::iter::once;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const EXPORT_HEADER: &str = "Windows Registry Editor Version 5.00";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Root {
+    CurrentUser,
+    LocalMachine
+}
+
+impl Root {
+    const ALL: [Self; 2] = [Self::CurrentUser, Self::LocalMachine];
+
+    /// Splits a key like `HKCU\Software\Game` into its root and the path under it.
+    fn split(key: &str) -> Option<(Self, String)> {
+        let key = key.replace('/', "\\");
+        let (root, path) = key.split_once('\\')?;
+        let root = match root.to_ascii_uppercase().as_str() {
+            "HKCU" | "HKEY_CURRENT_USER" => Self::CurrentUser,
+            "HKLM" | "HKEY_LOCAL_MACHINE" => Self::LocalMachine,
+            _ => return None
+        };
+        let path = path.trim_matches('\\');
+
+        (!path.is_empty()).then(|| (root, path.to_owned()))
+    }
+
+    /// The file in a prefix Wine keeps the root's keys in.
+    const fn file_name(self) -> &'static str {
+        match self {
+            Self::CurrentUser => "user.reg",
+            Self::LocalMachine => "system.reg"
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::CurrentUser => "HKEY_CURRENT_USER",
+            Self::LocalMachine => "HKEY_LOCAL_MACHINE"
+        }
+    }
+}
+
+/// A key and its values as they're written in a registry file, up to the next key.
+struct Section<'a> {
+    /// The key's path, unescaped.
+    path: String,
+    /// Everything from the key's header on, including the blank lines after it.
+    text: &'a str,
+    values: Vec<&'a str>
+}
+
+/// Splits a registry file into the text before its first key and each key's section.
+/// Wine escapes backslashes in key paths while exported `.reg` files don't, so `escaped` says which one `text` is.
+fn parse(text: &str, escaped: bool) -> (&str, Vec<Section<'_>>) {
+    let mut starts = vec![];
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.starts_with('[') {
+            starts.push(offset);
+        }
+
+        offset += line.len();
+    }
+
+    let preamble = &text[..starts.first().copied().unwrap_or(text.len())];
+    let sections = starts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &start)| {
+            let text = &text[start..starts.get(i + 1).copied().unwrap_or(text.len())];
+            let mut lines = text.lines();
+            let header = lines.next()?;
+            let path = &header[1..header.rfind(']')?];
+            let path = if escaped {
+                path.replace("\\\\", "\\")
+            } else {
+                path.to_owned()
+            };
+            let values = lines.filter(|line| !line.trim().is_empty() && !line.starts_with("#time=")).collect();
+
+            Some(Section { path, text, values })
+        })
+        .collect();
+
+    (preamble, sections)
+}
+
+/// Whether `path` is the key `key` or one of its subkeys.
+fn is_under(path: &str, key: &str) -> bool {
+    path.get(..key.len()).is_some_and(|start| start.eq_ignore_ascii_case(key))
+        && (path.len() == key.len() || path.as_bytes()[key.len()] == b'\\')
+}
+
+/// Exports `keys` and their subkeys from the registry of a Wine prefix in the format of `.reg` files.
+/// Each key is deleted before its values are added, so importing the export replaces whatever the keys had.
+/// Returns `None` when none of the keys exist.
+pub fn export(prefix: &Path, keys: &[String]) -> io::Result<Option<String>> {
+    let mut lines = vec![String::from(EXPORT_HEADER)];
+    let mut found = false;
+
+    for root in Root::ALL {
+        let paths: Vec<String> =
+            keys.iter().filter_map(|key| Root::split(key)).filter(|(r, _)| *r == root).map(|(_, path)| path).collect();
+        let file = prefix.join(root.file_name());
+
+        if paths.is_empty() || !file.is_file() {
+            continue;
+        }
+
+        let text = read_to_string(file)?;
+        let (_, sections) = parse(&text, true);
+
+        for path in &paths {
+            lines.push(format!("\n[-{}\\{path}]", root.name()));
+
+            for section in sections.iter().filter(|section| is_under(&section.path, path)) {
+                found = true;
+                lines.push(format!("\n[{}\\{}]", root.name(), section.path));
+                lines.extend(section.values.iter().map(|value| (*value).to_owned()));
+            }
+        }
+    }
+
+    Ok(found.then(|| lines.join("\n") + "\n"))
+}
+
+/// The files a Wine prefix keeps its registry in.
+pub fn files(prefix: &Path) -> impl Iterator<Item = PathBuf> {
+    Root::ALL.into_iter().map(|root| prefix.join(root.file_name()))
+}
+
+/// Imports exports made by [`export`] into the registry of a Wine prefix, returning each registry file that changes and its new text.
+/// Nothing is written, so the files can be replaced along with the rest of a restore.
+/// Keys an export deletes are removed with their subkeys before its keys are added.
+pub fn import(prefix: &Path, exports: &[String]) -> io::Result<Vec<(PathBuf, String)>> {
+    let sections: Vec<Section> = exports.iter().flat_map(|export| parse(export, false).1).collect();
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let mut updated_files = vec![];
+
+    for root in Root::ALL {
+        let mut deleted = vec![];
+        let mut added = vec![];
+
+        for section in &sections {
+            if let Some(key) = section.path.strip_prefix('-')
+                && let Some((r, path)) = Root::split(key)
+                && r == root
+            {
+                deleted.push(path);
+            } else if let Some((r, path)) = Root::split(&section.path)
+                && r == root
+            {
+                added.push((path, &section.values));
+            }
+        }
+
+        if deleted.is_empty() && added.is_empty() {
+            continue;
+        }
+
+        let file = prefix.join(root.file_name());
+        let text = if file.is_file() {
+            read_to_string(&file)?
+        } else {
+            String::from("WINE REGISTRY Version 2\n")
+        };
+        let (preamble, sections) = parse(&text, true);
+        let mut updated = String::from(preamble);
+
+        for section in sections.iter().filter(|section| !deleted.iter().any(|key| is_under(&section.path, key))) {
+            updated.push_str(section.text);
+        }
+
+        if !updated.ends_with("\n\n") {
+            updated.push('\n');
+        }
+
+        for (path, values) in added {
+            let header = format!("[{}] {timestamp}", path.replace('\\', "\\\\"));
+            let lines: Vec<&str> = once(header.as_str()).chain(values.iter().copied()).collect();
+
+            updated.push_str(&lines.join("\n"));
+            updated.push_str("\n\n");
+        }
+
+        updated_files.push((file, updated));
+    }
+
+    Ok(updated_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    const USER_REG: &str = r#"WINE REGISTRY Version 2
+;; All keys relative to \\User\\S-1-5-21-0-0-0-1000
+
+#arch=win64
+
+[Software\\Unit Test] 1700000000
+#time=1da0000000000000
+"Name"="Player"
+
+[Software\\Unit Test\\Progress] 1700000000
+#time=1da0000000000000
+"Chapter"=dword:00000003
+"Path"="C:\\Games\\Unit Test"
+
+[Software\\Unit Tests] 1700000000
+#time=1da0000000000000
+"Other"="game"
+
+[Software\\Wine] 1700000000
+#time=1da0000000000000
+"Version"="win10"
+
+"#;
+
+    fn prefix(test_name: &str) -> PathBuf {
+        let prefix = std::env::temp_dir().join("aletheia_tests").join(test_name);
+
+        if prefix.exists() {
+            remove_dir_all(&prefix).unwrap();
+        }
+
+        create_dir_all(&prefix).unwrap();
+        write(prefix.join("user.reg"), USER_REG).unwrap();
+        prefix
+    }
+
+    fn import_into(prefix: &Path, export: &str) {
+        for (file, text) in import(prefix, &[export.to_owned()]).unwrap() {
+            write(file, text).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let keys = vec![String::from("HKCU/Software/Unit Test")];
+        let source = prefix("registry_export_source");
+        let export = export(&source, &keys).unwrap().unwrap();
+
+        assert_eq!(
+            export,
+            r#"Windows Registry Editor Version 5.00
+
+[-HKEY_CURRENT_USER\Software\Unit Test]
+
+[HKEY_CURRENT_USER\Software\Unit Test]
+"Name"="Player"
+
+[HKEY_CURRENT_USER\Software\Unit Test\Progress]
+"Chapter"=dword:00000003
+"Path"="C:\\Games\\Unit Test"
+"#
+        );
+
+        assert!(super::export(&source, &[String::from("HKCU\\Software\\Missing")]).unwrap().is_none());
+
+        let target = prefix("registry_export_target");
+
+        write(target.join("user.reg"), USER_REG.replace("Player", "Other player")).unwrap();
+        import_into(&target, &export);
+
+        let imported = read_to_string(target.join("user.reg")).unwrap();
+
+        assert!(imported.starts_with("WINE REGISTRY Version 2\n"));
+        assert!(imported.contains("[Software\\\\Unit Test\\\\Progress] "));
+        assert!(!imported.contains("Other player"));
+        assert!(imported.contains("\"Other\"=\"game\""));
+        assert!(imported.contains("\"Version\"=\"win10\""));
+        assert_eq!(super::export(&target, &keys).unwrap().unwrap(), export);
+
+        // Subkeys that aren't in the export are removed
+        let progress = export.find("\n[HKEY_CURRENT_USER\\Software\\Unit Test\\Progress]").unwrap();
+        let without_progress = &export[..=progress];
+
+        import_into(&target, without_progress);
+
+        let imported = read_to_string(target.join("user.reg")).unwrap();
+
+        assert!(imported.contains("\"Name\"=\"Player\""));
+        assert!(!imported.contains("Chapter"));
+        assert!(imported.contains("\"Other\"=\"game\""));
+    }
+}
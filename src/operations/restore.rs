@@ -1,56 +1,1274 @@
 // SPDX-FileCopyrightText: 2025 Spencer
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::backup;
+use super::codec::{decode_file, encode_file, encoded_path, hash_stored};
+use super::conflict::{Conflict, Resolution};
+use super::encryption::{self, Key};
+use super::history::{self, History};
+use super::plan::{Action, Plan, Reason};
+use super::snapshot::{self, Snapshot, list_snapshots};
 use crate::config::Config;
 use crate::dirs::expand_path;
-use crate::file::hash_file;
-use crate::gamedb::GameInfo;
+use crate::file::{file_mode, hash_file, set_metadata};
+use crate::gamedb::{FileMetadata, GameDbEntry, GameInfo, Install, Layout, Platform};
 use crate::scanner::Game;
-use std::fs::{copy, create_dir_all};
-use std::path::Path;
+use crate::utils;
+use glob::Pattern;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{create_dir_all, remove_dir, remove_dir_all, remove_file, rename};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[cfg(unix)]
+use super::registry;
+#[cfg(unix)]
+use crate::dirs::existing_path;
+#[cfg(unix)]
+use std::fs::{read_to_string, write};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Game not found")]
     GameNotFound,
+    #[error("{0} is installed from {sources}, choose which one to restore to", sources = .1.join(", "))]
+    MultipleInstalls(String, Vec<String>),
+    #[error("No files in the snapshot match the given patterns")]
+    NoMatchingFiles,
+    #[error("Don't know where to restore {0}, the game's installation directory or prefix has to be given")]
+    UnknownLocation(String),
+    #[error("None of the saves backed up on {0} have an equivalent location on this platform")]
+    Unmapped(Platform),
     #[error("{0} is missing or corrupted")]
-    MissingOrCorruptedFiles(String)
+    MissingOrCorruptedFiles(String),
+    #[error("{0} failed to authenticate, it was either tampered with or encrypted with a different key")]
+    Authentication(String),
+    #[error("Backups are encrypted, but no key was provided")]
+    MissingKey,
+    #[error("Snapshot isn't encrypted and can't be authenticated, run rotate_key to encrypt existing backups")]
+    Unauthenticated,
+    #[error("Failed to read snapshots: {0}")]
+    Snapshot(#[from] snapshot::Error),
+    #[error("Failed to read sync history: {0}")]
+    History(#[from] history::Error),
+    #[error("Failed to keep the current saves: {0}")]
+    PreRestore(encryption::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to write {path}, nothing was restored: {error}", path = .0.display(), error = .1)]
+    Write(PathBuf, std::io::Error),
+    #[error("The last restore replaced the saves of another install from {0}, which has to be the one chosen to undo it")]
+    RestoredElsewhere(String),
+    #[error("Failed to import registry keys into the game's prefix: {0}")]
+    Registry(std::io::Error),
+    #[error(
+        "A previous restore was interrupted, put back or remove {} first",
+        .0.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    Interrupted(Vec<PathBuf>),
+    #[error("{0}")]
+    Conflict(Conflict),
+    #[error("{0}")]
+    LocalChanges(LocalChanges)
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
 
-pub fn restore_game(game_dir: &Path, manifest: &GameInfo, installed_games: &[Game], config: &Config) -> Result<bool> {
+/// How to handle saves on this device that a restore would replace.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RestoreOptions<'a> {
+    /// How to settle a conflict with another device, the restore fails with [`Error::Conflict`] when it's needed but not given.
+    pub resolution: Option<Resolution>,
+    /// Replaces saves that are newer than the snapshot or were never backed up.
+    pub force: bool,
+    /// Only plans the restore, without touching the saves or recording what this device synced with.
+    pub dry_run: bool,
+    /// Only restores files whose stored paths match one of these, or every file when empty.
+    /// Restoring some of the files doesn't sync this device with the snapshot, so it's never treated as a conflict.
+    pub files: &'a [Pattern],
+    /// Restores into this directory, laid out the way the snapshot stores files, instead of where the game keeps them.
+    pub to_dir: Option<&'a Path>,
+    /// Used instead of the detected game's installation directory.
+    pub game_root: Option<&'a Path>,
+    /// Used instead of the detected game's Wine prefix.
+    #[cfg(unix)]
+    pub prefix: Option<&'a Path>,
+    /// The launcher of the install to restore to, needed when the game is installed from more than one.
+    pub source: Option<&'a str>,
+    /// The game's entry, used to map saves backed up on another platform to where they're kept on this one.
+    pub entry: Option<&'a GameDbEntry>,
+    /// Also removes saves here that match the game's paths in [`RestoreOptions::entry`] but aren't in the snapshot,
+    /// so the saves end up exactly as they were backed up.
+    pub remove_extra: bool
+}
+
+impl RestoreOptions<'_> {
+    /// Whether files go somewhere other than where the detected game keeps them.
+    /// That doesn't replace the saves on this device, so it can't conflict, isn't recorded as synced and can't be undone.
+    const fn retargeted(&self) -> bool {
+        #[cfg(unix)]
+        let prefix = self.prefix.is_some();
+
+        #[cfg(windows)]
+        let prefix = false;
+
+        self.to_dir.is_some() || self.game_root.is_some() || prefix
+    }
+}
+
+/// Saves on this device a restore would have replaced or removed without [`RestoreOptions::force`].
+#[derive(Debug)]
+pub struct LocalChanges {
+    pub game: String,
+    /// The launcher of the install whose saves would be replaced.
+    pub source: String,
+    pub snapshot: String,
+    pub files: Vec<LocalChange>
+}
+
+#[derive(Debug)]
+pub struct LocalChange {
+    pub path: PathBuf,
+    /// Whether it was modified after the snapshot's copy, otherwise it isn't in any snapshot.
+    pub newer: bool
+}
+
+impl fmt::Display for LocalChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Restoring {} from snapshot {} would replace saves that are newer or were never backed up", self.game, self.snapshot)
+    }
+}
+
+/// With a key, only encrypted snapshots are restored, as anyone able to write to the save directory could have made the others.
+/// The saves it replaces are kept so it can be undone with [`undo_restore`].
+/// The game has to be installed unless `options` say where to restore to.
+/// Fails with [`Error::Conflict`] when the saves here and the snapshot from another device both changed since they were last synced,
+/// and with [`Error::LocalChanges`] when it would replace saves that are newer or were never backed up, unless `options` allow it.
+pub fn restore_game(
+    snapshot: &Snapshot, installed_games: &[Game], config: &Config, key: Option<&Key>, options: RestoreOptions
+) -> Result<Plan> {
     let steam_id = config.steam_account_id.as_deref();
+    let manifest = &snapshot.manifest;
     let game_name = &manifest.name;
 
-    let game = installed_games.iter().find(|g| g.name == *game_name).ok_or(Error::GameNotFound)?;
+    let game = target_game(game_name, installed_games, &options)?;
 
-    for file in &manifest.files {
-        let src_file = game_dir.join(Path::new(&file.path).file_name().unwrap());
+    // The export of the registry keys here is compared with the snapshot's like any other save
+    #[cfg(unix)]
+    if let Some(entry) = options.entry.filter(|_| options.to_dir.is_none()) {
+        backup::export_registry(&game, entry);
+    }
+
+    if key.is_some() && !snapshot.encrypted {
+        return Err(Error::Unauthenticated);
+    }
 
-        if !src_file.exists() || hash_file(&src_file) != file.hash {
-            return Err(Error::MissingOrCorruptedFiles(src_file.file_name().unwrap().to_string_lossy().to_string()));
+    let partial = !options.files.is_empty();
+    let mut selected = if partial {
+        Cow::Owned(snapshot.select_files(options.files))
+    } else {
+        Cow::Borrowed(snapshot)
+    };
+
+    if partial && selected.manifest.files.is_empty() && selected.manifest.deleted.is_empty() {
+        return Err(Error::NoMatchingFiles);
+    }
+
+    let extras = options
+        .entry
+        .filter(|_| options.remove_extra && options.to_dir.is_none())
+        .map_or_else(Vec::new, |entry| extra_files(&mut selected, &game, config, entry, options.files));
+
+    let (translations, unmapped) = if options.to_dir.is_none() {
+        map_paths(&mut selected, &game, steam_id, options.entry)
+    } else {
+        (HashMap::new(), vec![])
+    };
+
+    if !unmapped.is_empty() && selected.manifest.files.is_empty() && selected.manifest.deleted.is_empty() {
+        return Err(Error::Unmapped(manifest.platform.unwrap_or(Platform::current())));
+    }
+
+    let locate = |path: &str| {
+        options.to_dir.map_or_else(
+            || local_path(&game, steam_id, translations.get(path).map_or(path, String::as_str)),
+            |dir| dir.join(snapshot::stored_path(path))
+        )
+    };
+
+    // Placeholders that couldn't be expanded would otherwise be restored relative to the working directory
+    if let Some(file) = selected.manifest.files.iter().find(|file| !locate(&file.path).is_absolute()) {
+        return Err(Error::UnknownLocation(file.path.clone()));
+    }
+
+    // Skipping files that have nowhere to go here doesn't sync this device with the snapshot either
+    let syncs = !partial && unmapped.is_empty() && !options.retargeted();
+    let mut plan = plan_restore(&selected, &extras, &locate);
+
+    for path in unmapped {
+        let size = manifest.files.iter().find(|file| file.path == path).map_or(0, |file| file.size);
+
+        log::warn!("Skipped restoring {path} of {game_name}, it has no equivalent location on {}", Platform::current());
+        plan.add(PathBuf::from(path), Action::Skip, size, Reason::Unmapped);
+    }
+    let game_dir = config.save_dir.join(utils::sanitize_game_name(game_name).as_ref());
+    let snapshots = list_snapshots(&game_dir, key)?;
+    let synced = history::synced_snapshot(config, game_name)?;
+    let has_saves = manifest.files.iter().any(|file| locate(&file.path).exists());
+    let unchanged_since = |snapshot: &Snapshot| history::matches_local(snapshot, |path| Some(locate(path)));
+
+    let history = if syncs {
+        history::compare(&snapshots, synced.as_deref(), snapshot, &config.device_id, has_saves, unchanged_since)
+    } else {
+        History::Linear
+    };
+
+    if history == History::Diverged {
+        match options.resolution {
+            None => {
+                return Err(Error::Conflict(Conflict {
+                    game: game_name.clone(),
+                    snapshot: snapshot.id.clone(),
+                    hostname: manifest.hostname.clone()
+                }));
+            }
+            Some(Resolution::Local) => {
+                plan.skip_all(Reason::KeptLocal);
+                return Ok(plan);
+            }
+            Some(Resolution::Remote | Resolution::Both) => {}
         }
     }
 
-    for file in &manifest.files {
-        let file_path = Path::new(&file.path);
+    // When behind, the saves here are the ones last synced and the snapshot supersedes them, whatever their modification times
+    if !options.force && history != History::Behind {
+        let files = local_changes(&selected, &snapshots, &locate);
 
-        #[cfg(unix)]
-        let expanded = expand_path(file_path, game.installation_dir.as_deref(), game.prefix.as_deref(), steam_id);
+        if !files.is_empty() {
+            return Err(Error::LocalChanges(LocalChanges {
+                game: game_name.clone(),
+                source: game.source.clone(),
+                snapshot: snapshot.id.clone(),
+                files
+            }));
+        }
+    }
+
+    verify(&selected, key)?;
+
+    if options.dry_run {
+        return Ok(plan);
+    }
+
+    recover_interrupted(&selected, &game, &locate)?;
+
+    if !options.retargeted() {
+        save_pre_restore(&selected, &game, &game_dir, config, key, synced, &locate)?;
+    }
+
+    apply(&selected, &game, key, &locate)?;
+
+    if syncs {
+        history::mark_synced(config, game_name, Some(&snapshot.id))?;
+    }
+
+    Ok(plan)
+}
 
+/// Puts back the saves replaced by the last restore of a game on this device. Returns `false` when there's no restore to undo.
+/// The install is picked from `installed_games` like [`restore_game`] does, and has to be the one the restore replaced saves in.
+pub fn undo_restore(
+    game_name: &str, installed_games: &[Game], source: Option<&str>, config: &Config, key: Option<&Key>
+) -> Result<bool> {
+    let steam_id = config.steam_account_id.as_deref();
+    let game = &target_game(game_name, installed_games, &RestoreOptions { source, ..RestoreOptions::default() })?;
+    let game_dir = config.save_dir.join(utils::sanitize_game_name(game_name).as_ref());
+    let pre_restore_dir = snapshot::pre_restore_dir(&game_dir, &config.device_id);
+
+    let Some(pre_restore) = snapshot::read_snapshot(&pre_restore_dir, key)? else {
+        return Ok(false);
+    };
+
+    if key.is_some() && !pre_restore.encrypted {
+        return Err(Error::Unauthenticated);
+    }
+
+    if let Some(install) = &pre_restore.manifest.restored_to
+        && *install != installed(game)
+    {
+        return Err(Error::RestoredElsewhere(install.source.clone()));
+    }
+
+    let locate = |path: &str| local_path(game, steam_id, path);
+
+    verify(&pre_restore, key)?;
+    recover_interrupted(&pre_restore, game, &locate)?;
+    apply(&pre_restore, game, key, &locate)?;
+
+    history::mark_synced(config, &game.name, pre_restore.manifest.parent.as_deref())?;
+    remove_dir_all(pre_restore_dir)?;
+
+    Ok(true)
+}
+
+fn installed(game: &Game) -> Install {
+    Install {
+        source: game.source.clone(),
+        #[cfg(unix)]
+        prefix: game.prefix.clone(),
         #[cfg(windows)]
-        let expanded = expand_path(file_path, game.installation_dir.as_deref(), steam_id);
+        prefix: None
+    }
+}
 
-        let src_file = game_dir.join(file_path.file_name().unwrap());
+/// The game to restore, with its installation directory and prefix replaced by the ones in `options`.
+fn target_game(name: &str, installed_games: &[Game], options: &RestoreOptions) -> Result<Game> {
+    let installs: Vec<&Game> = installed_games.iter().filter(|g| g.name == name).collect();
 
-        if expanded.exists() && hash_file(&expanded) == file.hash {
+    let detected = match options.source {
+        Some(source) => installs.into_iter().find(|g| g.source.eq_ignore_ascii_case(source)),
+        // Which install it is doesn't matter when the saves don't go where it keeps them
+        None if installs.len() > 1 && options.retargeted() => None,
+        None if installs.len() > 1 => {
+            return Err(Error::MultipleInstalls(name.to_owned(), installs.iter().map(|g| g.source.clone()).collect()));
+        }
+        None => installs.first().copied()
+    };
+
+    if detected.is_none() && !options.retargeted() {
+        return Err(Error::GameNotFound);
+    }
+
+    Ok(Game {
+        name: name.to_owned(),
+        installation_dir: options.game_root.map(Path::to_owned).or_else(|| detected.and_then(|g| g.installation_dir.clone())),
+        #[cfg(unix)]
+        // Restoring to a directory leaves the prefix as it is
+        prefix: options
+            .prefix
+            .map(Path::to_owned)
+            .or_else(|| detected.and_then(|g| g.prefix.clone()))
+            .filter(|_| options.to_dir.is_none()),
+        source: detected.map(|g| g.source.clone()).unwrap_or_default(),
+        store_id: detected.and_then(|g| g.store_id.clone())
+    })
+}
+
+/// Where a recorded path is on this device. Directories in a prefix that only differ in case are used as they are.
+pub(super) fn local_path(game: &Game, steam_id: Option<&str>, path: &str) -> PathBuf {
+    #[cfg(unix)]
+    let expanded = expand_path(Path::new(path), game.installation_dir.as_deref(), game.prefix.as_deref(), steam_id);
+
+    #[cfg(unix)]
+    let expanded = match game.prefix.as_deref() {
+        Some(prefix) => existing_path(expanded, prefix),
+        None => expanded
+    };
+
+    #[cfg(windows)]
+    let expanded = expand_path(Path::new(path), game.installation_dir.as_deref(), steam_id);
+
+    expanded
+}
+
+/// Paths to restore stored files to instead, for saves backed up where the game keeps them somewhere else.
+/// Files from another platform with no equivalent location here are taken out of the snapshot and returned.
+fn map_paths(
+    snapshot: &mut Cow<Snapshot>, game: &Game, steam_id: Option<&str>, entry: Option<&GameDbEntry>
+) -> (HashMap<String, String>, Vec<String>) {
+    let platform = Platform::current();
+    let foreign = snapshot.manifest.platform.is_some_and(|made_on| made_on != platform);
+
+    // Games in a prefix keep their saves where Windows does
+    #[cfg(unix)]
+    let targets = if game.prefix.is_some() {
+        vec![Platform::Windows, platform]
+    } else {
+        vec![platform]
+    };
+
+    #[cfg(windows)]
+    let targets = vec![platform];
+
+    let mut translations = HashMap::new();
+    let mut unmapped = vec![];
+    let manifest = &snapshot.manifest;
+
+    for path in manifest.files.iter().map(|file| &file.path).chain(&manifest.deleted) {
+        if local_path(game, steam_id, path).is_absolute() {
             continue;
         }
 
-        create_dir_all(expanded.parent().unwrap()).unwrap();
-        copy(&src_file, &expanded).unwrap();
+        let translated = entry.and_then(|entry| {
+            targets
+                .iter()
+                .filter_map(|target| entry.translate(path, *target))
+                .find(|translated| local_path(game, steam_id, translated).is_absolute())
+        });
+
+        match translated {
+            Some(translated) => {
+                translations.insert(path.clone(), translated);
+            }
+            None if foreign => unmapped.push(path.clone()),
+            None => {}
+        }
     }
 
-    Ok(true)
+    if !unmapped.is_empty() {
+        let manifest = &mut snapshot.to_mut().manifest;
+
+        manifest.files.retain(|file| !unmapped.contains(&file.path));
+        manifest.deleted.retain(|path| !unmapped.contains(path));
+    }
+
+    (translations, unmapped)
+}
+
+/// Saves here matching the game's paths that aren't in the snapshot, which are added to the ones it removes.
+/// Only ones matching `patterns` are, when restoring some of the files, and excluded ones never are.
+fn extra_files(snapshot: &mut Cow<Snapshot>, game: &Game, config: &Config, entry: &GameDbEntry, patterns: &[Pattern]) -> Vec<String> {
+    let steam_id = config.steam_account_id.as_deref();
+    let manifest = &snapshot.manifest;
+    let extras: Vec<String> = backup::find_files(game, entry, config)
+        .0
+        .iter()
+        .map(|file| backup::shrunk_path(game, steam_id, file))
+        .filter(|path| patterns.is_empty() || patterns.iter().any(|pattern| pattern.matches(path)))
+        .filter(|path| !manifest.files.iter().any(|file| file.path == *path) && !manifest.deleted.contains(path))
+        .collect();
+
+    if !extras.is_empty() {
+        snapshot.to_mut().manifest.deleted.extend(extras.iter().cloned());
+    }
+
+    extras
+}
+
+fn plan_restore(snapshot: &Snapshot, extras: &[String], locate: &impl Fn(&str) -> PathBuf) -> Plan {
+    let manifest = &snapshot.manifest;
+    let mut plan = Plan::new(&manifest.name, Some(snapshot.id.clone()));
+
+    for file in &manifest.files {
+        let path = locate(&file.path);
+        let (action, reason) = if !path.exists() {
+            (Action::Create, Reason::Missing)
+        } else if hash_file(&path) == file.hash {
+            (Action::Skip, Reason::Unchanged)
+        } else {
+            (Action::Overwrite, Reason::Differs)
+        };
+
+        plan.add(path, action, file.size, reason);
+    }
+
+    for deleted in &manifest.deleted {
+        let path = locate(deleted);
+
+        if !path.is_file() {
+            continue;
+        }
+
+        let size = path.metadata().map_or(0, |metadata| metadata.len());
+        let reason = if extras.contains(deleted) {
+            Reason::NotInSnapshot
+        } else {
+            Reason::Deleted
+        };
+
+        plan.add(path, Action::Remove, size, reason);
+    }
+
+    plan
+}
+
+/// Saves here that `snapshot` would replace or remove, and that are either newer than its copy or aren't in any snapshot.
+fn local_changes(snapshot: &Snapshot, snapshots: &[Snapshot], locate: &impl Fn(&str) -> PathBuf) -> Vec<LocalChange> {
+    let backed_up: HashSet<(&str, &str)> =
+        snapshots.iter().flat_map(|s| &s.manifest.files).map(|file| (file.path.as_str(), file.hash.as_str())).collect();
+    let mut changes = vec![];
+
+    for file in &snapshot.manifest.files {
+        let path = locate(&file.path);
+
+        if !path.is_file() {
+            continue;
+        }
+
+        let hash = hash_file(&path);
+
+        if hash == file.hash {
+            continue;
+        }
+
+        let newer = path.metadata().and_then(|metadata| metadata.modified()).is_ok_and(|modified| modified > file.modified);
+
+        if newer || !backed_up.contains(&(file.path.as_str(), hash.as_str())) {
+            changes.push(LocalChange { path, newer });
+        }
+    }
+
+    for deleted in &snapshot.manifest.deleted {
+        let path = locate(deleted);
+
+        if path.is_file() && !backed_up.contains(&(deleted.as_str(), hash_file(&path).as_str())) {
+            changes.push(LocalChange { path, newer: false });
+        }
+    }
+
+    changes
+}
+
+fn verify(snapshot: &Snapshot, key: Option<&Key>) -> Result<()> {
+    for file in &snapshot.manifest.files {
+        let src_file = snapshot.file_path(file);
+
+        match hash_stored(&src_file, file, key) {
+            Ok(hash) if hash == file.hash => {}
+            Err(encryption::Error::Authentication) => return Err(Error::Authentication(file.path.clone())),
+            Err(encryption::Error::MissingKey) => return Err(Error::MissingKey),
+            _ => return Err(Error::MissingOrCorruptedFiles(file.path.clone()))
+        }
+    }
+
+    Ok(())
+}
+
+/// Stages every file next to where it goes before moving any of them in, so a failure part way leaves the saves as they were.
+/// Registry keys restored with the snapshot are imported into the game's prefix as part of the same transaction.
+fn apply(snapshot: &Snapshot, game: &Game, key: Option<&Key>, locate: &impl Fn(&str) -> PathBuf) -> Result<()> {
+    let mut transaction = Transaction::default();
+    let result = transaction.stage(snapshot, game, key, locate).and_then(|()| transaction.commit(&snapshot.manifest.deleted, locate));
+
+    if result.is_ok() {
+        transaction.finish();
+    } else {
+        transaction.roll_back();
+    }
+
+    result
+}
+
+/// Files written by a restore, tracked so they can be rolled back.
+#[derive(Default)]
+struct Transaction {
+    /// Directories created for new files, outermost first.
+    created_dirs: Vec<PathBuf>,
+    /// Decoded files and where they go.
+    staged: Vec<(PathBuf, PathBuf)>,
+    /// What has been moved in or aside so far, in order.
+    steps: Vec<Step>
+}
+
+enum Step {
+    /// A file that didn't exist before.
+    Added(PathBuf),
+    /// A file replaced or removed by the restore, kept aside until it either finishes or is rolled back.
+    MovedAside {
+        path: PathBuf,
+        aside: PathBuf
+    }
+}
+
+impl Transaction {
+    #[cfg_attr(not(unix), expect(unused_variables, reason = "Only games in Wine prefixes have registry keys to restore"))]
+    fn stage(&mut self, snapshot: &Snapshot, game: &Game, key: Option<&Key>, locate: &impl Fn(&str) -> PathBuf) -> Result<()> {
+        for file in &snapshot.manifest.files {
+            let expanded = locate(&file.path);
+
+            if expanded.exists() && hash_file(&expanded) == file.hash {
+                continue;
+            }
+
+            self.create_parent_dirs(&expanded)?;
+
+            let staged = suffixed_path(&expanded, "partial");
+            // Tracked first so a partly written file is removed on failure as well
+            self.staged.push((staged.clone(), expanded.clone()));
+
+            match decode_file(&snapshot.file_path(file), file, key, &staged) {
+                Ok(()) => {}
+                Err(encryption::Error::Io(e)) => return Err(Error::Write(expanded, e)),
+                Err(_) => return Err(Error::MissingOrCorruptedFiles(file.path.clone()))
+            }
+
+            // Restored saves look as old as the ones backed up, so they aren't mistaken for newer ones
+            set_metadata(&staged, file.modified, file.mode).map_err(|e| Error::Write(expanded, e))?;
+        }
+
+        #[cfg(unix)]
+        if let Some(prefix) = &game.prefix {
+            self.stage_registry(snapshot, prefix, locate)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stages the prefix's registry files with the keys in the snapshot's registry exports imported.
+    #[cfg(unix)]
+    fn stage_registry(&mut self, snapshot: &Snapshot, prefix: &Path, locate: &impl Fn(&str) -> PathBuf) -> Result<()> {
+        let mut exports = vec![];
+
+        for file in snapshot.manifest.files.iter().filter(|file| file.path.starts_with("{WineRegistry}")) {
+            let expanded = locate(&file.path);
+            // Exports that didn't change aren't staged
+            let export = self.staged.iter().find(|(_, path)| *path == expanded).map_or(&expanded, |(staged, _)| staged);
+
+            exports.push(read_to_string(export).map_err(Error::Registry)?);
+        }
+
+        if exports.is_empty() {
+            return Ok(());
+        }
+
+        for (path, text) in registry::import(prefix, &exports).map_err(Error::Registry)? {
+            let staged = suffixed_path(&path, "partial");
+            self.staged.push((staged.clone(), path.clone()));
+
+            write(&staged, text).map_err(|e| Error::Write(path, e))?;
+        }
+
+        Ok(())
+    }
+
+    fn create_parent_dirs(&mut self, path: &Path) -> Result<()> {
+        let parent = path.parent().unwrap();
+        let missing: Vec<&Path> = parent.ancestors().take_while(|dir| !dir.exists()).collect();
+
+        create_dir_all(parent).map_err(|e| Error::Write(parent.to_owned(), e))?;
+        self.created_dirs.extend(missing.into_iter().rev().map(Path::to_owned));
+
+        Ok(())
+    }
+
+    fn commit(&mut self, deleted: &[String], locate: &impl Fn(&str) -> PathBuf) -> Result<()> {
+        for (staged, path) in std::mem::take(&mut self.staged) {
+            if path.exists() {
+                self.move_aside(&path)?;
+            } else {
+                self.steps.push(Step::Added(path.clone()));
+            }
+
+            if let Err(e) = rename(&staged, &path) {
+                // Still removed when rolling back
+                self.staged.push((staged, path.clone()));
+                return Err(Error::Write(path, e));
+            }
+        }
+
+        for path in deleted.iter().map(|path| locate(path)).filter(|path| path.is_file()) {
+            self.move_aside(&path)?;
+        }
+
+        Ok(())
+    }
+
+    fn move_aside(&mut self, path: &Path) -> Result<()> {
+        let aside = suffixed_path(path, "aletheia-old");
+
+        rename(path, &aside).map_err(|e| Error::Write(path.to_owned(), e))?;
+        self.steps.push(Step::MovedAside { path: path.to_owned(), aside });
+
+        Ok(())
+    }
+
+    fn finish(self) {
+        for step in self.steps {
+            if let Step::MovedAside { aside, .. } = step
+                && let Err(e) = remove_file(&aside)
+            {
+                log::warn!("Failed to remove {} after restoring: {e}", aside.display());
+            }
+        }
+    }
+
+    fn roll_back(self) {
+        for (staged, _) in self.staged {
+            if staged.exists()
+                && let Err(e) = remove_file(&staged)
+            {
+                log::error!("Failed to remove {} while rolling back a restore: {e}", staged.display());
+            }
+        }
+
+        for step in self.steps.into_iter().rev() {
+            let result = match &step {
+                Step::Added(path) => remove_file(path),
+                Step::MovedAside { path, aside } => rename(aside, path)
+            };
+
+            if let Err(e) = result {
+                let (Step::Added(path) | Step::MovedAside { path, .. }) = step;
+                log::error!("Failed to roll back {} while rolling back a restore: {e}", path.display());
+            }
+        }
+
+        // Only empty directories are removed, so anything else written to them in the meantime is kept
+        for dir in self.created_dirs.into_iter().rev() {
+            let _ = remove_dir(dir);
+        }
+    }
+}
+
+/// Removes files staged by a restore that was interrupted before moving them in.
+/// Fails with [`Error::Interrupted`] when it was interrupted after moving saves aside, as they may be the only copy of a save.
+#[cfg_attr(not(unix), expect(unused_variables, reason = "Only games in Wine prefixes have registry keys to restore"))]
+fn recover_interrupted(snapshot: &Snapshot, game: &Game, locate: &impl Fn(&str) -> PathBuf) -> Result<()> {
+    let manifest = &snapshot.manifest;
+    let paths = manifest.files.iter().map(|file| &file.path).chain(&manifest.deleted).map(|path| locate(path));
+    #[cfg(unix)]
+    let paths = paths.chain(game.prefix.iter().flat_map(|prefix| registry::files(prefix)));
+    let mut moved_aside = vec![];
+
+    for path in paths {
+        let staged = suffixed_path(&path, "partial");
+        let aside = suffixed_path(&path, "aletheia-old");
+
+        if staged.is_file() {
+            log::warn!("Removing {} left by an interrupted restore", staged.display());
+            remove_file(&staged).map_err(|e| Error::Write(staged, e))?;
+        }
+
+        if aside.exists() {
+            moved_aside.push(aside);
+        }
+    }
+
+    if moved_aside.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Interrupted(moved_aside))
+    }
+}
+
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut suffixed = path.as_os_str().to_owned();
+    suffixed.push(".");
+    suffixed.push(suffix);
+    suffixed.into()
+}
+
+/// Keeps the saves `snapshot` would replace or remove, and which of its files don't exist yet, so the restore can be undone.
+/// The previous copy is kept when the restore wouldn't change anything.
+fn save_pre_restore(
+    snapshot: &Snapshot, game: &Game, game_dir: &Path, config: &Config, key: Option<&Key>, synced: Option<String>,
+    locate: &impl Fn(&str) -> PathBuf
+) -> Result<()> {
+    let manifest = &snapshot.manifest;
+    let mut replaced = vec![];
+    let mut missing = vec![];
+
+    for file in &manifest.files {
+        let expanded = locate(&file.path);
+
+        if !expanded.exists() {
+            missing.push(file.path.clone());
+        } else if hash_file(&expanded) != file.hash {
+            replaced.push((file.path.clone(), expanded));
+        }
+    }
+
+    replaced.extend(manifest.deleted.iter().map(|path| (path.clone(), locate(path))).filter(|(_, expanded)| expanded.is_file()));
+
+    if replaced.is_empty() && missing.is_empty() {
+        return Ok(());
+    }
+
+    let pre_restore_dir = snapshot::pre_restore_dir(game_dir, &config.device_id);
+    let partial = pre_restore_dir.with_extension("partial");
+    let compression = config.compression_for(&manifest.name);
+    let mut files = vec![];
+
+    if partial.exists() {
+        remove_dir_all(&partial)?;
+    }
+
+    for (path, expanded) in replaced {
+        let metadata = expanded.metadata()?;
+        let mut file = FileMetadata {
+            hash: hash_file(&expanded),
+            modified: metadata.modified()?,
+            path,
+            size: metadata.len(),
+            compression,
+            stored_size: None,
+            encrypted: key.is_some(),
+            mode: file_mode(&metadata)
+        };
+        let dest = encoded_path(partial.join(snapshot::stored_path(&file.path)), &file);
+
+        create_dir_all(dest.parent().unwrap())?;
+
+        let stored_size = encode_file(&expanded, &dest, compression, key).map_err(Error::PreRestore)?;
+
+        if !compression.is_none() || key.is_some() {
+            file.stored_size = Some(stored_size);
+        }
+
+        files.push(file);
+    }
+
+    let pre_restore = GameInfo {
+        name: manifest.name.clone(),
+        created: Some(SystemTime::now()),
+        pinned: false,
+        tag: None,
+        layout: Layout::Tree,
+        device: Some(config.device_id.clone()),
+        hostname: history::hostname(),
+        // Undoing the restore puts back what this device was synced with
+        parent: synced,
+        conflict: false,
+        platform: Some(Platform::current()),
+        files,
+        deleted: missing,
+        restored_to: Some(installed(game))
+    };
+
+    create_dir_all(&partial)?;
+    snapshot::write_manifest(&partial, &pre_restore, key)?;
+
+    if pre_restore_dir.exists() {
+        remove_dir_all(&pre_restore_dir)?;
+    }
+
+    Ok(rename(partial, pre_restore_dir)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamedb::GameDbEntry;
+    use crate::operations::backup::tests::{entry_matching, fixture, write_files};
+    use crate::operations::backup::{BackupOptions, backup_game};
+    use crate::operations::snapshot::latest_snapshot;
+    use std::fs::{read_dir, read_to_string, write};
+
+    /// Matches files in the game's installation directory and its subdirectories.
+    fn entry() -> GameDbEntry {
+        entry_matching("['{GameRoot}/*', '{GameRoot}/*/*']")
+    }
+
+    /// Backs up the files in `files`, relative to the game's installation directory, and returns the snapshot.
+    fn back_up(test_name: &str, files: &[(&str, &str)]) -> (PathBuf, Config, Game, Snapshot) {
+        let (root, config, game) = fixture(test_name);
+
+        write_files(&root.join("game"), files);
+
+        assert!(backup_game(&game, &config, &entry(), None, BackupOptions::default()).unwrap().changed());
+
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        (root, config, game, snapshot)
+    }
+
+    #[test]
+    fn test_undo_restore() {
+        let (root, config, game, snapshot) = back_up("undo_restore", &[("save.dat", "backed up")]);
+        let save = root.join("game/save.dat");
+
+        let force = RestoreOptions { force: true, ..RestoreOptions::default() };
+
+        write(&save, "progress").unwrap();
+        assert!(restore_game(&snapshot, std::slice::from_ref(&game), &config, None, force).unwrap().changed());
+        assert_eq!(read_to_string(&save).unwrap(), "backed up");
+        assert!(undo_restore("Unit Test", std::slice::from_ref(&game), None, &config, None).unwrap());
+        assert_eq!(read_to_string(&save).unwrap(), "progress");
+        assert!(!undo_restore("Unit Test", std::slice::from_ref(&game), None, &config, None).unwrap());
+
+        // Files the restore created are removed again
+        remove_file(&save).unwrap();
+        restore_game(&snapshot, std::slice::from_ref(&game), &config, None, RestoreOptions::default()).unwrap();
+        assert!(save.exists());
+        assert!(undo_restore("Unit Test", std::slice::from_ref(&game), None, &config, None).unwrap());
+        assert!(!save.exists());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_failed_restore_rolls_back() {
+        let (root, config, game, snapshot) = back_up("failed_restore", &[("a.dat", "backed up"), ("slot/b.dat", "backed up")]);
+
+        write(root.join("game/a.dat"), "progress").unwrap();
+        remove_dir_all(root.join("game/slot")).unwrap();
+        // b.dat can't be written as its directory is now a file
+        write(root.join("game/slot"), "").unwrap();
+
+        let force = RestoreOptions { force: true, ..RestoreOptions::default() };
+        let result = restore_game(&snapshot, std::slice::from_ref(&game), &config, None, force);
+
+        assert!(matches!(result, Err(Error::Write(..))));
+        assert_eq!(read_to_string(root.join("game/a.dat")).unwrap(), "progress");
+        assert!(!root.join("game/a.dat.partial").exists());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_restore() {
+        let (root, config, game, snapshot) = back_up("interrupted_restore", &[("save.dat", "backed up")]);
+        let save = root.join("game/save.dat");
+        let force = RestoreOptions { force: true, ..RestoreOptions::default() };
+
+        write(&save, "progress").unwrap();
+        write(root.join("game/save.dat.partial"), "").unwrap();
+        write(root.join("game/save.dat.aletheia-old"), "older progress").unwrap();
+
+        let result = restore_game(&snapshot, std::slice::from_ref(&game), &config, None, force);
+
+        assert!(matches!(result, Err(Error::Interrupted(paths)) if paths == [root.join("game/save.dat.aletheia-old")]));
+        assert_eq!(read_to_string(&save).unwrap(), "progress");
+        assert_eq!(read_to_string(root.join("game/save.dat.aletheia-old")).unwrap(), "older progress");
+        assert!(!root.join("game/save.dat.partial").exists());
+        assert!(!undo_restore("Unit Test", std::slice::from_ref(&game), None, &config, None).unwrap());
+
+        remove_file(root.join("game/save.dat.aletheia-old")).unwrap();
+        assert!(restore_game(&snapshot, std::slice::from_ref(&game), &config, None, force).unwrap().changed());
+        assert_eq!(read_to_string(&save).unwrap(), "backed up");
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_restore_keeps_local_changes() {
+        let (root, config, game, snapshot) = back_up("local_changes", &[("save.dat", "backed up")]);
+        let save = root.join("game/save.dat");
+        let games = std::slice::from_ref(&game);
+
+        write(&save, "progress").unwrap();
+
+        let Err(Error::LocalChanges(changes)) = restore_game(&snapshot, games, &config, None, RestoreOptions::default()) else {
+            panic!("Restore replaced saves that were never backed up");
+        };
+
+        assert_eq!(changes.files.len(), 1);
+        assert_eq!(changes.files[0].path, save);
+        assert_eq!(read_to_string(&save).unwrap(), "progress");
+
+        let force = RestoreOptions { force: true, ..RestoreOptions::default() };
+        assert!(restore_game(&snapshot, games, &config, None, force).unwrap().changed());
+        assert_eq!(read_to_string(&save).unwrap(), "backed up");
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let (root, config, game, snapshot) = back_up("dry_run", &[("a.dat", "backed up"), ("b.dat", "backed up")]);
+        let games = std::slice::from_ref(&game);
+        let entry: GameDbEntry =
+            serde_yaml::from_str("files:\n  windows: ['{GameRoot}/*']\n  linux: ['{GameRoot}/*']\n  mac: ['{GameRoot}/*']").unwrap();
+
+        write(root.join("game/a.dat"), "progress").unwrap();
+        remove_file(root.join("game/b.dat")).unwrap();
+
+        let backup = backup_game(&game, &config, &entry, None, BackupOptions { dry_run: true, ..BackupOptions::default() }).unwrap();
+
+        assert_eq!(backup.files.len(), 2);
+        assert_eq!((backup.files[0].action, backup.files[0].reason), (Action::Copy, Reason::Changed));
+        assert_eq!((backup.files[1].action, backup.files[1].reason), (Action::Remove, Reason::DeletedLocally));
+        assert_eq!(list_snapshots(&config.save_dir.join("Unit Test"), None).unwrap().len(), 1);
+
+        let options = RestoreOptions { force: true, dry_run: true, ..RestoreOptions::default() };
+        let restore = restore_game(&snapshot, games, &config, None, options).unwrap();
+        let actions: Vec<Action> = restore.files.iter().map(|file| file.action).collect();
+
+        assert_eq!(actions, [Action::Overwrite, Action::Create]);
+        assert_eq!(read_to_string(root.join("game/a.dat")).unwrap(), "progress");
+        assert!(!root.join("game/b.dat").exists());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_restore_directory_trees() {
+        let (root, config, game, snapshot) = back_up("directory_trees_restore", &[("profiles/default.sav", "backed up")]);
+
+        remove_dir_all(root.join("game")).unwrap();
+        restore_game(&snapshot, std::slice::from_ref(&game), &config, None, RestoreOptions::default()).unwrap();
+        assert_eq!(read_to_string(root.join("game/profiles/default.sav")).unwrap(), "backed up");
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_restore_selected_files() {
+        let (root, config, game, snapshot) = back_up("selected_files", &[("slot1.sav", "backed up"), ("slot2.sav", "backed up")]);
+        // Restoring some of the files doesn't record the snapshot as synced
+        history::mark_synced(&config, "Unit Test", None).unwrap();
+
+        remove_file(root.join("game/slot1.sav")).unwrap();
+        remove_file(root.join("game/slot2.sav")).unwrap();
+
+        let files = [Pattern::new("*slot2*").unwrap()];
+        let options = RestoreOptions { files: &files, ..RestoreOptions::default() };
+
+        assert!(restore_game(&snapshot, std::slice::from_ref(&game), &config, None, options).unwrap().changed());
+        assert!(!root.join("game/slot1.sav").exists());
+        assert_eq!(read_to_string(root.join("game/slot2.sav")).unwrap(), "backed up");
+        assert_eq!(history::synced_snapshot(&config, "Unit Test").unwrap(), None);
+
+        let files = [Pattern::new("*slot3*").unwrap()];
+        let options = RestoreOptions { files: &files, ..RestoreOptions::default() };
+
+        assert!(matches!(restore_game(&snapshot, std::slice::from_ref(&game), &config, None, options), Err(Error::NoMatchingFiles)));
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_restore_elsewhere() {
+        let (root, config, _, snapshot) = back_up("restore_elsewhere", &[("save.dat", "backed up")]);
+        let synced = history::synced_snapshot(&config, "Unit Test").unwrap();
+
+        // The game doesn't need to be installed when it's clear where its saves go
+        assert!(matches!(restore_game(&snapshot, &[], &config, None, RestoreOptions::default()), Err(Error::GameNotFound)));
+
+        let to_dir = root.join("inspect");
+        let options = RestoreOptions { to_dir: Some(&to_dir), ..RestoreOptions::default() };
+
+        restore_game(&snapshot, &[], &config, None, options).unwrap();
+        assert_eq!(read_to_string(to_dir.join("{GameRoot}/save.dat")).unwrap(), "backed up");
+
+        let game_root = root.join("moved");
+        let options = RestoreOptions { game_root: Some(&game_root), ..RestoreOptions::default() };
+
+        restore_game(&snapshot, &[], &config, None, options).unwrap();
+        assert_eq!(read_to_string(game_root.join("save.dat")).unwrap(), "backed up");
+
+        // Neither touched the saves on this device, so there's nothing to undo or sync
+        assert_eq!(history::synced_snapshot(&config, "Unit Test").unwrap(), synced);
+        assert!(!snapshot::pre_restore_dir(&config.save_dir.join("Unit Test"), &config.device_id).exists());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_restore_chooses_install() {
+        let (root, config, game, snapshot) = back_up("restore_chooses_install", &[("save.dat", "backed up")]);
+        let other = Game { installation_dir: Some(root.join("other")), source: "Other".into(), ..game.clone() };
+        let installs = [game, other];
+
+        let result = restore_game(&snapshot, &installs, &config, None, RestoreOptions::default());
+        assert!(matches!(result, Err(Error::MultipleInstalls(_, sources)) if sources == ["Test", "Other"]));
+
+        let to_dir = root.join("elsewhere");
+        let options = RestoreOptions { to_dir: Some(&to_dir), ..RestoreOptions::default() };
+
+        restore_game(&snapshot, &installs, &config, None, options).unwrap();
+        assert_eq!(read_to_string(to_dir.join("{GameRoot}/save.dat")).unwrap(), "backed up");
+
+        let options = RestoreOptions { source: Some("other"), ..RestoreOptions::default() };
+
+        restore_game(&snapshot, &installs, &config, None, options).unwrap();
+        assert_eq!(read_to_string(root.join("other/save.dat")).unwrap(), "backed up");
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_undo_restore_of_install() {
+        let (root, config, game, snapshot) = back_up("undo_restore_of_install", &[("save.dat", "backed up")]);
+        let other = Game { installation_dir: Some(root.join("other")), source: "Other".into(), ..game.clone() };
+        let installs = [game, other];
+        let options = RestoreOptions { source: Some("Other"), ..RestoreOptions::default() };
+
+        write_files(&root.join("other"), &[("save.dat", "progress")]);
+        restore_game(&snapshot, &installs, &config, None, RestoreOptions { force: true, ..options }).unwrap();
+
+        let result = undo_restore("Unit Test", &installs, None, &config, None);
+        assert!(matches!(result, Err(Error::MultipleInstalls(..))));
+
+        // The other install's saves are left alone
+        let result = undo_restore("Unit Test", &installs, Some("Test"), &config, None);
+        assert!(matches!(result, Err(Error::RestoredElsewhere(source)) if source == "Other"));
+        assert_eq!(read_to_string(root.join("game/save.dat")).unwrap(), "backed up");
+
+        assert!(undo_restore("Unit Test", &installs, Some("Other"), &config, None).unwrap());
+        assert_eq!(read_to_string(root.join("other/save.dat")).unwrap(), "progress");
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_restore_from_other_platform() {
+        let root = std::env::temp_dir().join("aletheia_restore_from_other_platform_test");
+        let _ = remove_dir_all(&root);
+        let config = Config { save_dir: root.join("saves"), device_id: "test".into(), ..Config::default() };
+        let prefix = root.join("prefix");
+        let windows_game = Game {
+            name: "Unit Test".into(),
+            installation_dir: None,
+            prefix: Some(prefix.clone()),
+            source: "Test".into(),
+            store_id: None
+        };
+        let native_game = Game { installation_dir: Some(root.join("game")), prefix: None, ..windows_game.clone() };
+        let entry: GameDbEntry =
+            serde_yaml::from_str("files:\n  windows: ['{Documents}/Unit Test/*']\n  linux: ['{GameRoot}/saves/*']\nequivalent: true")
+                .unwrap();
+        let save = prefix.join("drive_c/users/steamuser/Documents/Unit Test/save.dat");
+
+        create_dir_all(save.parent().unwrap()).unwrap();
+        write(&save, "backed up").unwrap();
+        backup_game(&windows_game, &config, &entry, None, BackupOptions::default()).unwrap();
+
+        let mut snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        snapshot.manifest.platform = Some(Platform::Windows);
+
+        let options = RestoreOptions { entry: Some(&entry), ..RestoreOptions::default() };
+
+        restore_game(&snapshot, std::slice::from_ref(&native_game), &config, None, options).unwrap();
+        assert_eq!(read_to_string(root.join("game/saves/save.dat")).unwrap(), "backed up");
+
+        // Without the entry declaring the saves equivalent, there's nowhere to put them
+        let result = restore_game(&snapshot, &[native_game], &config, None, RestoreOptions::default());
+        assert!(matches!(result, Err(Error::Unmapped(Platform::Windows))));
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_restore_registry_keys() {
+        let root = std::env::temp_dir().join("aletheia_restore_registry_keys_test");
+        let _ = remove_dir_all(&root);
+        let config = Config { save_dir: root.join("saves"), device_id: "test".into(), ..Config::default() };
+        let prefix = root.join("prefix");
+        let game = Game {
+            name: "Unit Test".into(),
+            installation_dir: None,
+            prefix: Some(prefix.clone()),
+            source: "Test".into(),
+            store_id: None
+        };
+        let entry: GameDbEntry =
+            serde_yaml::from_str("files:\n  windows: ['{Documents}/Unit Test/*']\nregistry: ['HKCU\\Software\\Unit Test']").unwrap();
+        let user_reg =
+            |chapter: &str| format!("WINE REGISTRY Version 2\n\n[Software\\\\Unit Test] 1700000000\n\"Chapter\"=dword:{chapter}\n\n");
+
+        create_dir_all(&prefix).unwrap();
+        write(prefix.join("user.reg"), user_reg("00000001")).unwrap();
+        backup_game(&game, &config, &entry, None, BackupOptions::default()).unwrap();
+
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        assert!(snapshot.manifest.files.iter().any(|file| file.path == "{WineRegistry}/Unit Test.reg"));
+
+        write(prefix.join("user.reg"), user_reg("00000002")).unwrap();
+
+        // Planning leaves the prefix alone
+        let options = RestoreOptions { entry: Some(&entry), force: true, dry_run: true, ..RestoreOptions::default() };
+
+        restore_game(&snapshot, std::slice::from_ref(&game), &config, None, options).unwrap();
+        assert_eq!(read_dir(&prefix).unwrap().count(), 1);
+        assert_eq!(read_to_string(prefix.join("user.reg")).unwrap(), user_reg("00000002"));
+
+        let options = RestoreOptions { dry_run: false, ..options };
+
+        // Failing to import the keys rolls back the files restored with them
+        create_dir_all(prefix.join("user.reg.partial")).unwrap();
+
+        let result = restore_game(&snapshot, std::slice::from_ref(&game), &config, None, options);
+
+        assert!(matches!(result, Err(Error::Write(..))));
+        assert_eq!(read_to_string(prefix.join("user.reg")).unwrap(), user_reg("00000002"));
+
+        remove_dir_all(prefix.join("user.reg.partial")).unwrap();
+        restore_game(&snapshot, std::slice::from_ref(&game), &config, None, options).unwrap();
+        assert!(read_to_string(prefix.join("user.reg")).unwrap().contains("\"Chapter\"=dword:00000001"));
+
+        assert!(undo_restore("Unit Test", std::slice::from_ref(&game), None, &config, None).unwrap());
+        assert!(read_to_string(prefix.join("user.reg")).unwrap().contains("\"Chapter\"=dword:00000002"));
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_prefix_casing() {
+        let (root, config, game) = fixture("prefix_casing_restore");
+        let game = Game { installation_dir: None, prefix: Some(root.join("prefix")), ..game };
+        let other_game = Game { prefix: Some(root.join("other_prefix")), ..game.clone() };
+        let entry: GameDbEntry = serde_yaml::from_str("files:\n  windows: ['{Documents}/My Games/Savegames/*.sav']").unwrap();
+        let other_saves = root.join("other_prefix/drive_c/users/steamuser/Documents/My Games/savegames");
+
+        write_files(&root.join("prefix/drive_c/users/steamuser/Documents/my games/SaveGames"), &[("Slot1.sav", "backed up")]);
+        create_dir_all(&other_saves).unwrap();
+        backup_game(&game, &config, &entry, None, BackupOptions::default()).unwrap();
+
+        // Restoring writes into the directories there, whatever their casing
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        let options = RestoreOptions { entry: Some(&entry), ..RestoreOptions::default() };
+        restore_game(&snapshot, &[other_game], &config, None, options).unwrap();
+
+        assert_eq!(read_to_string(other_saves.join("Slot1.sav")).unwrap(), "backed up");
+        assert!(!root.join("other_prefix/drive_c/users/steamuser/Documents/my games").exists());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_deleted_files() {
+        let (root, config, game, _) = back_up("deleted_files", &[("save.dat", "kept"), ("slot.dat", "deleted")]);
+        let slot = root.join("game/slot.dat");
+        let extra = root.join("game/extra.dat");
+
+        remove_file(&slot).unwrap();
+
+        let plan = backup_game(&game, &config, &entry(), None, BackupOptions::default()).unwrap();
+        assert!(plan.files.iter().any(|file| file.path == slot && file.reason == Reason::DeletedLocally));
+
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        assert_eq!(snapshot.manifest.deleted, ["{GameRoot}/slot.dat"]);
+
+        // The deleted file was backed up before, so restoring removes it without needing to be forced
+        write(&slot, "deleted").unwrap();
+        write(&extra, "extra").unwrap();
+        restore_game(&snapshot, std::slice::from_ref(&game), &config, None, RestoreOptions::default()).unwrap();
+        assert!(!slot.exists());
+        assert!(extra.exists());
+
+        let entry = entry();
+        let options = RestoreOptions { force: true, entry: Some(&entry), remove_extra: true, ..RestoreOptions::default() };
+
+        restore_game(&snapshot, std::slice::from_ref(&game), &config, None, options).unwrap();
+        assert!(!extra.exists());
+        assert_eq!(read_to_string(root.join("game/save.dat")).unwrap(), "kept");
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_restore_keeps_metadata() {
+        let (root, config, game, _) = back_up("restore_keeps_metadata", &[("other.dat", "other")]);
+        let save = root.join("game/save.dat");
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+
+        write(&save, "backed up").unwrap();
+        crate::file::set_metadata(&save, modified, Some(0o600)).unwrap();
+        backup_game(&game, &config, &entry(), None, BackupOptions::default()).unwrap();
+
+        let snapshot = latest_snapshot(&config.save_dir.join("Unit Test"), None).unwrap().unwrap();
+        let file = snapshot.manifest.files.iter().find(|file| file.path == "{GameRoot}/save.dat").unwrap();
+
+        assert_eq!(file.modified, modified);
+        assert_eq!(snapshot.file_path(file).metadata().unwrap().modified().unwrap(), modified);
+
+        remove_file(&save).unwrap();
+        restore_game(&snapshot, std::slice::from_ref(&game), &config, None, RestoreOptions::default()).unwrap();
+
+        let metadata = save.metadata().unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+
+        #[cfg(unix)]
+        {
+            assert_eq!(file.mode, Some(0o600));
+            assert_eq!(file_mode(&metadata), Some(0o600));
+        }
+
+        remove_dir_all(root).unwrap();
+    }
 }
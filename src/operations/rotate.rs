@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::blob::collect_garbage;
+use super::encryption::{self, Key};
+use super::snapshot::{self, pre_restore_snapshots, read_snapshots, write_manifest};
+use crate::gamedb::{Compression, FileMetadata, Layout};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, hard_link, read, read_dir, remove_file, rename};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to read snapshots: {0}")]
+    Snapshot(#[from] snapshot::Error),
+    #[error("Failed to re-encrypt backups: {0}")]
+    Encryption(#[from] encryption::Error)
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Re-encrypts every backup in `save_dir` with a key derived from `secret`, or decrypts them when `secret` is `None`.
+/// Backups that aren't encrypted yet are encrypted as well, so this also enables encryption for existing backups.
+/// An interrupted rotation is finished by running it again with the same `secret`. Returns how many snapshots were rewritten.
+pub fn rotate_key(save_dir: &Path, old: Option<&Key>, secret: Option<&[u8]>) -> Result<usize> {
+    let new = secret.map(|secret| encryption::begin_rotation(save_dir, secret, old)).transpose()?;
+    // The new key can also decrypt files encrypted with the old one
+    let reader = new.as_ref().or(old);
+    let mut rotated_blobs = HashSet::new();
+    let mut rotated = 0;
+
+    for entry in read_dir(save_dir)? {
+        let game_dir = entry?.path();
+
+        if !game_dir.is_dir() || game_dir.file_name().unwrap().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        // Stored files shared between snapshots through hard links keep being shared
+        let mut rotated_files: HashMap<(String, Compression), PathBuf> = HashMap::new();
+
+        // The saves kept to undo a restore are rotated too, so the restore can still be undone
+        let pre_restore = pre_restore_snapshots(&game_dir, reader)?;
+
+        for mut snapshot in read_snapshots(&game_dir, reader, true)?.into_iter().chain(pre_restore) {
+            let mut files = std::mem::take(&mut snapshot.manifest.files);
+            let mut stale = vec![];
+
+            for file in &mut files {
+                let source = snapshot.file_path(file);
+                let mut rotated_file = FileMetadata { encrypted: new.is_some(), ..file.clone() };
+                let dest = snapshot.file_path(&rotated_file);
+
+                if snapshot.manifest.layout == Layout::Blobs {
+                    // Blobs in their old encoding are removed by garbage collection once nothing references them
+                    if rotated_blobs.insert(dest.clone()) {
+                        reencrypt(&source, &dest, file, reader, new.as_ref())?;
+                    }
+                } else {
+                    let shared = rotated_files.get(&(file.hash.clone(), file.compression));
+
+                    if shared.is_none_or(|shared| replace_with_link(shared, &dest).is_err()) {
+                        reencrypt(&source, &dest, file, reader, new.as_ref())?;
+                        rotated_files.insert((file.hash.clone(), file.compression), dest.clone());
+                    }
+
+                    if source != dest {
+                        stale.push(source);
+                    }
+                }
+
+                rotated_file.stored_size =
+                    (!rotated_file.compression.is_none() || rotated_file.encrypted).then_some(dest.metadata()?.len());
+                *file = rotated_file;
+            }
+
+            snapshot.manifest.files = files;
+
+            // Old copies are only removed once the manifest points to the new ones
+            write_manifest(&snapshot.path, &snapshot.manifest, new.as_ref())?;
+
+            for stale in stale {
+                remove_file(stale)?;
+            }
+
+            rotated += 1;
+        }
+    }
+
+    encryption::finish_rotation(save_dir, new.is_some())?;
+    collect_garbage(save_dir, new.as_ref(), &HashSet::new(), false)?;
+
+    Ok(rotated)
+}
+
+/// Compressed files stay compressed, only their encryption changes.
+fn reencrypt(source: &Path, dest: &Path, file: &FileMetadata, reader: Option<&Key>, new: Option<&Key>) -> Result<()> {
+    let data = read(source)?;
+    let data = if file.encrypted {
+        reader.ok_or(encryption::Error::MissingKey)?.decrypt(&data)?
+    } else {
+        data
+    };
+    let partial = partial_path(dest);
+    let mut partial_file = File::create(&partial)?;
+
+    match new {
+        Some(key) => partial_file.write_all(&key.encrypt(&data))?,
+        None => partial_file.write_all(&data)?
+    }
+
+    partial_file.sync_all()?;
+    Ok(rename(partial, dest)?)
+}
+
+fn replace_with_link(original: &Path, dest: &Path) -> std::io::Result<()> {
+    if original == dest {
+        return Ok(());
+    }
+
+    let partial = partial_path(dest);
+
+    hard_link(original, &partial)?;
+    rename(partial, dest)
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    partial.into()
+}
@@ -0,0 +1,444 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::blob::blob_path;
+use super::codec::encoded_path;
+use super::encryption::{self, Key};
+use crate::file::hash_file;
+use crate::gamedb::{FileMetadata, GameInfo, Layout};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use glob::Pattern;
+use std::collections::HashSet;
+use std::fs::{File, copy, create_dir_all, read, read_dir, remove_file, rename, write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub const MANIFEST_FILE: &str = "aletheia_manifest.yaml";
+const SNAPSHOTS_DIR: &str = "snapshots";
+const PRE_RESTORE_DIR: &str = "pre_restore";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Malformed manifest file")]
+    MalformedManifest,
+    #[error("Failed to read manifest: {0}")]
+    Encryption(#[from] encryption::Error)
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+pub enum SnapshotSelector {
+    Latest,
+    Id(String),
+    /// The newest snapshot created within a period, such as the day or minute a user gave.
+    At(Range<SystemTime>),
+    AsOf(SystemTime)
+}
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub id: String,
+    pub path: PathBuf,
+    pub manifest: GameInfo,
+    /// Whether the manifest was encrypted, and so authenticated when it was read.
+    pub encrypted: bool
+}
+
+impl Snapshot {
+    pub fn created(&self) -> SystemTime {
+        self.manifest.created.unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+
+    /// Where the backed up copy of `file` is stored.
+    pub fn file_path(&self, file: &FileMetadata) -> PathBuf {
+        match self.manifest.layout {
+            Layout::Flat => self.path.join(Path::new(&file.path).file_name().unwrap()),
+            Layout::Tree => encoded_path(self.path.join(stored_path(&file.path)), file),
+            // Snapshots live in <save_dir>/<game>/snapshots/<id>, and pre-restore ones in <save_dir>/<game>/pre_restore/<device>
+            Layout::Blobs => blob_path(self.path.ancestors().nth(3).unwrap(), file)
+        }
+    }
+
+    /// The snapshot with only the files whose stored paths match one of `patterns`.
+    pub fn select_files(&self, patterns: &[Pattern]) -> Self {
+        let matches = |path: &str| patterns.iter().any(|pattern| pattern.matches(path));
+        let mut selected = self.clone();
+
+        selected.manifest.files.retain(|file| matches(&file.path));
+        selected.manifest.deleted.retain(|path| matches(path));
+        selected
+    }
+
+    /// Pinned and tagged snapshots are never pruned.
+    pub const fn is_protected(&self) -> bool {
+        self.manifest.pinned || self.manifest.tag.is_some()
+    }
+}
+
+/// Returns every complete snapshot of a game, oldest first, without writing anything.
+/// A snapshot only counts once its manifest has been written, so interrupted backups are ignored.
+/// Encrypted manifests that fail to authenticate are never skipped, as that would hide tampering.
+pub fn list_snapshots(game_dir: &Path, key: Option<&Key>) -> Result<Vec<Snapshot>> {
+    read_snapshots(game_dir, key, false)
+}
+
+/// Like [`list_snapshots`], but `strict` fails on malformed manifests instead of skipping them.
+pub(super) fn read_snapshots(game_dir: &Path, key: Option<&Key>, strict: bool) -> Result<Vec<Snapshot>> {
+    let snapshots_dir = game_dir.join(SNAPSHOTS_DIR);
+    let mut snapshots = vec![];
+
+    if !snapshots_dir.exists() {
+        return Ok(snapshots);
+    }
+
+    for entry in read_dir(&snapshots_dir)? {
+        let path = entry?.path();
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        let snapshot = match read_snapshot(&path, key) {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => continue,
+            Err(Error::MalformedManifest) if !strict => {
+                let id = path.file_name().unwrap().to_string_lossy();
+                log::warn!("Skipping snapshot {id} in {} as its manifest is malformed.", game_dir.display());
+                continue;
+            }
+            Err(e) => return Err(e)
+        };
+
+        snapshots.push(snapshot);
+    }
+
+    snapshots.sort_by(|a, b| a.created().cmp(&b.created()).then_with(|| a.id.cmp(&b.id)));
+
+    Ok(snapshots)
+}
+
+/// Reads the snapshot in `path`, which is `None` until its manifest has been written.
+pub(super) fn read_snapshot(path: &Path, key: Option<&Key>) -> Result<Option<Snapshot>> {
+    let manifest_path = path.join(MANIFEST_FILE);
+
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+
+    let id = path.file_name().unwrap().to_string_lossy().to_string();
+    let data = read(&manifest_path)?;
+    let encrypted = encryption::is_encrypted(&data);
+    let manifest = serde_yaml::from_slice::<GameInfo>(&encryption::open(data, key)?).map_err(|_| Error::MalformedManifest)?;
+
+    Ok(Some(Snapshot { id, path: path.to_owned(), manifest, encrypted }))
+}
+
+/// Where the saves replaced by the last restore on a device are kept, so it can be undone.
+/// It's laid out like a snapshot, but kept apart so it's never listed, selected or pruned.
+pub fn pre_restore_dir(game_dir: &Path, device_id: &str) -> PathBuf {
+    game_dir.join(PRE_RESTORE_DIR).join(device_id)
+}
+
+/// Returns the pre-restore snapshots of every device, see [`pre_restore_dir`].
+pub(super) fn pre_restore_snapshots(game_dir: &Path, key: Option<&Key>) -> Result<Vec<Snapshot>> {
+    let pre_restore_dir = game_dir.join(PRE_RESTORE_DIR);
+    let mut snapshots = vec![];
+
+    if !pre_restore_dir.exists() {
+        return Ok(snapshots);
+    }
+
+    for entry in read_dir(pre_restore_dir)? {
+        let path = entry?.path();
+
+        if path.is_dir()
+            && let Some(snapshot) = read_snapshot(&path, key)?
+        {
+            snapshots.push(snapshot);
+        }
+    }
+
+    Ok(snapshots)
+}
+
+pub fn latest_snapshot(game_dir: &Path, key: Option<&Key>) -> Result<Option<Snapshot>> {
+    Ok(select_snapshot(list_snapshots(game_dir, key)?, &SnapshotSelector::Latest))
+}
+
+impl SnapshotSelector {
+    /// Interprets a user supplied snapshot reference, either an ID, a tag or the local time it was created at.
+    pub fn parse(input: &str) -> Self {
+        parse_period(input).map_or_else(|| Self::Id(input.to_owned()), Self::At)
+    }
+}
+
+/// Picks a snapshot from a list returned by [`list_snapshots`].
+/// [`SnapshotSelector::AsOf`] picks the newest snapshot created before the given time.
+/// Conflict copies are only picked when they're selected directly.
+pub fn select_snapshot(mut snapshots: Vec<Snapshot>, selector: &SnapshotSelector) -> Option<Snapshot> {
+    let index = match selector {
+        SnapshotSelector::Latest => snapshots.iter().rposition(|s| !s.manifest.conflict),
+        SnapshotSelector::Id(id) => snapshots.iter().position(|s| s.id == *id || s.manifest.tag.as_ref() == Some(id)),
+        SnapshotSelector::At(period) => snapshots.iter().rposition(|s| period.contains(&s.created())),
+        SnapshotSelector::AsOf(time) => snapshots.iter().rposition(|s| s.created() < *time && !s.manifest.conflict)
+    }?;
+
+    Some(snapshots.swap_remove(index))
+}
+
+/// Parses a local date (`2025-01-31`) or date and time (`2025-01-31 18:30[:00]`).
+pub fn parse_time(input: &str) -> Option<SystemTime> {
+    parse_period(input).map(|period| period.start)
+}
+
+/// Parses the same inputs as [`parse_time`] into the whole day, minute or second they cover.
+fn parse_period(input: &str) -> Option<Range<SystemTime>> {
+    let input = input.trim().replacen('T', " ", 1);
+    let (start, length) = [("%Y-%m-%d %H:%M:%S", TimeDelta::seconds(1)), ("%Y-%m-%d %H:%M", TimeDelta::minutes(1))]
+        .iter()
+        .find_map(|(format, length)| NaiveDateTime::parse_from_str(&input, format).ok().map(|naive| (naive, *length)))
+        .or_else(|| {
+            NaiveDate::parse_from_str(&input, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|naive| (naive, TimeDelta::days(1)))
+        })?;
+    let local = |naive| Local.from_local_datetime(&naive).earliest().map(SystemTime::from);
+
+    Some(local(start)?..local(start + length)?)
+}
+
+/// Maps a shrunk path such as `{LocalLow}/Studio/Game/slot1/save.dat` to a relative path that is safe to create on any OS.
+pub fn stored_path(file_path: &str) -> PathBuf {
+    file_path
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != ".")
+        .map(|component| {
+            if component == ".." {
+                return "_".to_owned();
+            }
+
+            // Drive letters and characters NTFS doesn't support
+            component.trim_end_matches(':').replace([':', '*', '?', '"', '<', '>', '|'], "_")
+        })
+        .collect()
+}
+
+/// Reserves a new, empty snapshot directory named after `time`.
+pub fn create(game_dir: &Path, time: SystemTime) -> Result<(String, PathBuf)> {
+    let snapshots_dir = game_dir.join(SNAPSHOTS_DIR);
+    let base_id = DateTime::<Utc>::from(time).format("%Y%m%dT%H%M%SZ").to_string();
+    let mut id = base_id.clone();
+    let mut suffix = 1;
+
+    while snapshots_dir.join(&id).exists() {
+        suffix += 1;
+        id = format!("{base_id}-{suffix}");
+    }
+
+    let path = snapshots_dir.join(&id);
+    create_dir_all(&path)?;
+
+    Ok((id, path))
+}
+
+/// Encrypts the manifest when a key is given. It's written to a temporary file first so an interrupted write can't corrupt it.
+pub fn write_manifest(snapshot_dir: &Path, manifest: &GameInfo, key: Option<&Key>) -> Result<()> {
+    let data = serde_yaml::to_string(manifest).map_err(|_| Error::MalformedManifest)?;
+    let partial = snapshot_dir.join(MANIFEST_FILE).with_extension("partial");
+
+    match key {
+        Some(key) => write(&partial, key.encrypt(data.as_bytes()))?,
+        None => write(&partial, data)?
+    }
+
+    Ok(rename(partial, snapshot_dir.join(MANIFEST_FILE))?)
+}
+
+/// Brings the backups of every game up to date with the current layout, see [`migrate_legacy`] and [`migrate_flat_layout`].
+/// Runs once at startup, so nothing has to be migrated while snapshots are listed.
+pub fn migrate_backups(save_dir: &Path) -> Result<()> {
+    if !save_dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in read_dir(save_dir)? {
+        let game_dir = entry?.path();
+
+        if !game_dir.is_dir() {
+            continue;
+        }
+
+        migrate_legacy(&game_dir)?;
+
+        let snapshots_dir = game_dir.join(SNAPSHOTS_DIR);
+
+        if !snapshots_dir.exists() {
+            continue;
+        }
+
+        for entry in read_dir(snapshots_dir)? {
+            // Flat snapshots predate encryption, so manifests that can't be read without a key never need migrating
+            if let Ok(Some(mut snapshot)) = read_snapshot(&entry?.path(), None)
+                && snapshot.manifest.layout == Layout::Flat
+            {
+                migrate_flat_layout(&mut snapshot)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves a backup made before snapshots existed (files and manifest directly inside the game directory) into its own snapshot.
+fn migrate_legacy(game_dir: &Path) -> Result<()> {
+    let manifest_path = game_dir.join(MANIFEST_FILE);
+
+    if !manifest_path.exists() {
+        return Ok(());
+    }
+
+    let mut manifest: GameInfo = serde_yaml::from_reader(File::open(&manifest_path)?).map_err(|_| Error::MalformedManifest)?;
+    let created = manifest.files.iter().map(|file| file.modified).max().unwrap_or_else(SystemTime::now);
+    let (id, snapshot_dir) = create(game_dir, created)?;
+
+    for file in &manifest.files {
+        let file_name = Path::new(&file.path).file_name().unwrap();
+        let legacy_file = game_dir.join(file_name);
+
+        if legacy_file.exists() {
+            rename(&legacy_file, snapshot_dir.join(file_name))?;
+        }
+    }
+
+    manifest.created = Some(created);
+    write_manifest(&snapshot_dir, &manifest, None)?;
+    remove_file(&manifest_path)?;
+
+    log::info!("Migrated existing backup of {} to snapshot {id}.", manifest.name);
+
+    Ok(())
+}
+
+/// Moves the files of a snapshot made before directory structure was preserved into [`Layout::Tree`].
+/// Files whose names collided only kept one copy, so only entries matching the stored file's hash receive it.
+fn migrate_flat_layout(snapshot: &mut Snapshot) -> Result<()> {
+    let mut flat_files = HashSet::new();
+
+    for file in &snapshot.manifest.files {
+        let flat_file = snapshot.path.join(Path::new(&file.path).file_name().unwrap());
+
+        if !flat_file.exists() || hash_file(&flat_file) != file.hash {
+            log::warn!("{} is missing from snapshot {} of {}.", file.path, snapshot.id, snapshot.manifest.name);
+            continue;
+        }
+
+        let dest = snapshot.path.join(stored_path(&file.path));
+
+        if dest == flat_file {
+            continue;
+        }
+
+        create_dir_all(dest.parent().unwrap())?;
+        copy(&flat_file, &dest)?;
+        flat_files.insert(flat_file);
+    }
+
+    for flat_file in flat_files {
+        remove_file(flat_file)?;
+    }
+
+    snapshot.manifest.layout = Layout::Tree;
+    write_manifest(&snapshot.path, &snapshot.manifest, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(id: &str, created: &str) -> Snapshot {
+        let created = Local.from_local_datetime(&NaiveDateTime::parse_from_str(created, "%Y-%m-%d %H:%M:%S").unwrap()).unwrap();
+
+        Snapshot {
+            id: id.to_owned(),
+            path: PathBuf::from(id),
+            manifest: GameInfo {
+                name: "Unit Test".into(),
+                created: Some(created.into()),
+                pinned: false,
+                tag: None,
+                layout: Layout::Tree,
+                device: None,
+                hostname: None,
+                parent: None,
+                conflict: false,
+                platform: None,
+                files: vec![],
+                deleted: vec![],
+                restored_to: None
+            },
+            encrypted: false
+        }
+    }
+
+    #[test]
+    fn test_select_at() {
+        let snapshots = vec![
+            snapshot("a", "2025-01-30 23:59:59"),
+            snapshot("b", "2025-01-31 18:30:15"),
+            snapshot("c", "2025-01-31 18:30:45"),
+            snapshot("d", "2025-01-31 18:31:00"),
+            snapshot("e", "2025-02-01 00:00:00"),
+        ];
+        let select = |input: &str| select_snapshot(snapshots.clone(), &SnapshotSelector::parse(input)).map(|s| s.id);
+
+        assert_eq!(select("2025-01-31").as_deref(), Some("d"));
+        assert_eq!(select("2025-01-31 18:30").as_deref(), Some("c"));
+        assert_eq!(select("2025-01-31T18:30:15").as_deref(), Some("b"));
+        assert_eq!(select("2025-01-31 18:29"), None);
+        assert_eq!(select("2025-01-29"), None);
+    }
+
+    #[test]
+    fn test_migrate_backups() {
+        let save_dir = std::env::temp_dir().join("aletheia_migrate_backups_test");
+        let game_dir = save_dir.join("Unit Test");
+        let _ = std::fs::remove_dir_all(&save_dir);
+
+        create_dir_all(&game_dir).unwrap();
+        write(game_dir.join("save.dat"), "backed up").unwrap();
+
+        let mut manifest = snapshot("legacy", "2025-01-31 18:30:00").manifest;
+        manifest.created = None;
+        manifest.layout = Layout::Flat;
+        manifest.files.push(FileMetadata {
+            hash: hash_file(&game_dir.join("save.dat")),
+            modified: SystemTime::UNIX_EPOCH,
+            path: "{GameRoot}/slot/save.dat".into(),
+            size: 9,
+            compression: crate::gamedb::Compression::None,
+            stored_size: None,
+            encrypted: false,
+            mode: None
+        });
+        write(game_dir.join(MANIFEST_FILE), serde_yaml::to_string(&manifest).unwrap()).unwrap();
+
+        // Listing leaves old backups as they are
+        assert!(list_snapshots(&game_dir, None).unwrap().is_empty());
+        assert!(game_dir.join(MANIFEST_FILE).exists());
+
+        migrate_backups(&save_dir).unwrap();
+
+        let snapshots = list_snapshots(&game_dir, None).unwrap();
+
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].manifest.layout, Layout::Tree);
+        assert_eq!(read(snapshots[0].file_path(&snapshots[0].manifest.files[0])).unwrap(), b"backed up");
+        assert!(!game_dir.join(MANIFEST_FILE).exists());
+
+        std::fs::remove_dir_all(save_dir).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod directory;
+mod rclone;
+mod s3;
+mod sftp;
+mod webdav;
+
+use super::snapshot::MANIFEST_FILE;
+use crate::config::{Config, StorageBackend};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, create_dir_all, read, read_dir, remove_file, rename, write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("SFTP error: {0}")]
+    Sftp(#[from] ssh2::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{0} failed with {1}")]
+    Status(String, reqwest::StatusCode),
+    #[error("Malformed response: {0}")]
+    MalformedResponse(String),
+    #[error("Missing credentials, set {0}")]
+    MissingCredentials(&'static str),
+    #[error("rclone error: {0}")]
+    Rclone(#[from] rclone::Error),
+    #[error("Host key of {0} isn't trusted, connect to it with ssh once to add it to known_hosts")]
+    UnknownHost(String)
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Remembers what was synced last, to tell changes made elsewhere apart from ones made locally.
+const STATE_FILE: &str = ".mirror.json";
+
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+struct State {
+    backend: StorageBackend,
+    remote: HashSet<String>,
+    local: HashMap<String, SystemTime>
+}
+
+/// Paths are relative to the root of the storage and always separated by `/`.
+pub trait Storage {
+    /// Lists every file along with its size.
+    fn list(&self) -> Result<HashMap<String, u64>>;
+    fn read(&self, path: &str) -> Result<Vec<u8>>;
+    fn write(&self, path: &str, data: &[u8]) -> Result<()>;
+    /// Deleting a file that doesn't exist isn't an error.
+    fn delete(&self, path: &str) -> Result<()>;
+}
+
+fn open_storage(backend: &StorageBackend) -> Result<Option<Box<dyn Storage>>> {
+    Ok(Some(match backend {
+        StorageBackend::Local => return Ok(None),
+        StorageBackend::Directory { path } => Box::new(directory::Directory::new(path.clone())),
+        StorageBackend::Sftp { host, port, username, path, private_key } => {
+            Box::new(sftp::Sftp::connect(host, *port, username, path, private_key.as_deref())?)
+        }
+        StorageBackend::WebDav { url, username } => Box::new(webdav::WebDav::new(url, username.clone())?),
+        StorageBackend::S3 { endpoint, region, bucket, prefix, path_style } => {
+            Box::new(s3::S3::new(endpoint, region, bucket, prefix, *path_style)?)
+        }
+        StorageBackend::Rclone { remote } => Box::new(rclone::Rclone::new(remote))
+    }))
+}
+
+/// Mirrors remote storage into the save directory, so operations can keep working with local files.
+/// Files changed on both sides keep the local changes, and only files removed locally are deleted when pushing,
+/// so backups made elsewhere in the meantime are kept.
+pub struct Mirror {
+    storage: Box<dyn Storage>,
+    backend: StorageBackend,
+    save_dir: PathBuf,
+    /// Remote files as of the last sync.
+    remote: HashMap<String, u64>,
+    /// When each local file was last modified as of the last sync.
+    local: HashMap<String, SystemTime>
+}
+
+/// Downloads every file missing from the save directory, returning `None` when backups are stored locally.
+pub fn pull_storage(config: &Config) -> Result<Option<Mirror>> {
+    let Some(storage) = open_storage(&config.storage)? else {
+        return Ok(None);
+    };
+
+    let mut mirror = Mirror {
+        storage,
+        backend: config.storage.clone(),
+        save_dir: config.save_dir.clone(),
+        remote: HashMap::new(),
+        local: HashMap::new()
+    };
+    mirror.pull()?;
+
+    Ok(Some(mirror))
+}
+
+impl Mirror {
+    fn pull(&mut self) -> Result<()> {
+        create_dir_all(&self.save_dir)?;
+
+        let synced = self.read_state();
+        let remote = self.storage.list()?;
+        let local = local_files(&self.save_dir)?;
+        // Changed locally without being pushed, such as when a previous push failed
+        let changed: HashSet<&String> = local
+            .iter()
+            .filter(|(path, modified)| synced.local.get(*path).is_some_and(|synced| synced != *modified))
+            .map(|(path, _)| path)
+            .collect();
+
+        for (path, size) in &remote {
+            let local_path = self.save_dir.join(path);
+
+            // Manifests can be changed in place, while every other file keeps its contents once written
+            if is_partial(path)
+                || changed.contains(path)
+                || (!is_mutable(path) && local_path.metadata().is_ok_and(|metadata| metadata.len() == *size))
+            {
+                continue;
+            }
+
+            let partial = partial_path(&local_path);
+
+            create_dir_all(local_path.parent().unwrap())?;
+            write(&partial, self.storage.read(path)?)?;
+            rename(&partial, &local_path)?;
+        }
+
+        for path in local.keys() {
+            if synced.remote.contains(path) && !remote.contains_key(path) && !changed.contains(path) {
+                log::info!("Removing {path} as it was deleted from storage.");
+                remove_file(self.save_dir.join(path))?;
+            }
+        }
+
+        let mut synced_local = local_files(&self.save_dir)?;
+
+        // Keeping the previous time makes the next push upload them
+        for path in changed {
+            synced_local.insert(path.clone(), synced.local[path]);
+        }
+
+        self.remote = remote;
+        self.local = synced_local;
+        self.write_state()
+    }
+
+    /// Files synced with a different backend are treated as never synced, so they're uploaded instead of removed.
+    fn read_state(&self) -> State {
+        File::open(self.save_dir.join(STATE_FILE))
+            .ok()
+            .and_then(|file| serde_json::from_reader::<File, State>(file).ok())
+            .filter(|state| state.backend == self.backend)
+            .unwrap_or_default()
+    }
+
+    fn write_state(&self) -> Result<()> {
+        let state = State {
+            backend: self.backend.clone(),
+            remote: self.remote.keys().cloned().collect(),
+            local: self.local.clone()
+        };
+        serde_json::to_writer(File::create(self.save_dir.join(STATE_FILE))?, &state).map_err(std::io::Error::from)?;
+
+        Ok(())
+    }
+
+    /// Uploads every file added or changed locally since the last sync and deletes the ones removed locally.
+    /// Manifests are uploaded last and deleted first, so snapshots are never seen with missing files.
+    pub fn push(&mut self) -> Result<()> {
+        let local = local_files(&self.save_dir)?;
+        let mut uploads: Vec<&String> = local
+            .iter()
+            .filter(|(path, modified)| self.local.get(*path) != Some(modified) || !self.remote.contains_key(*path))
+            .map(|(path, _)| path)
+            .collect();
+        // Partial files belong to uploads from other devices that are still in progress
+        let mut deletions: Vec<&String> = self.remote.keys().filter(|path| !local.contains_key(*path) && !is_partial(path)).collect();
+
+        uploads.sort_by_key(|path| path.ends_with(MANIFEST_FILE));
+        deletions.sort_by_key(|path| !path.ends_with(MANIFEST_FILE));
+
+        for path in uploads {
+            self.storage.write(path, &read(self.save_dir.join(path))?)?;
+        }
+
+        for path in deletions {
+            self.storage.delete(path)?;
+        }
+
+        self.remote = self.storage.list()?;
+        self.local = local;
+        self.write_state()
+    }
+}
+
+fn check(response: reqwest::blocking::Response, action: &str) -> Result<reqwest::blocking::Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(Error::Status(format!("{action} {}", response.url()), response.status()))
+    }
+}
+
+/// Manifests and encryption parameters.
+fn is_mutable(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| extension == "yaml")
+}
+
+fn is_partial(path: &str) -> bool {
+    path.ends_with(".partial")
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    partial.into()
+}
+
+fn local_files(save_dir: &Path) -> Result<HashMap<String, SystemTime>> {
+    let mut files = HashMap::new();
+
+    for path in walk(save_dir)? {
+        let relative = path.strip_prefix(save_dir).unwrap();
+        let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+
+        if !is_partial(&relative) && relative != STATE_FILE {
+            files.insert(relative, path.metadata()?.modified()?);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Returns every file below `dir`.
+fn walk(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+
+    if !dir.exists() {
+        return Ok(files);
+    }
+
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            files.extend(walk(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, remove_dir_all};
+
+    #[test]
+    fn test_mirror() {
+        let root = std::env::temp_dir().join("aletheia_mirror_test");
+        let remote_dir = root.join("remote");
+        let save_dir = root.join("cache");
+        let _ = remove_dir_all(&root);
+        let storage = directory::Directory::new(remote_dir.clone());
+
+        storage.write("Game/snapshots/1/{GameRoot}/save.dat", b"save").unwrap();
+        storage.write("Game/snapshots/1/aletheia_manifest.yaml", b"name: Game").unwrap();
+
+        let mut mirror = Mirror {
+            storage: Box::new(storage),
+            backend: StorageBackend::Directory { path: remote_dir.clone() },
+            save_dir: save_dir.clone(),
+            remote: HashMap::new(),
+            local: HashMap::new()
+        };
+        mirror.pull().unwrap();
+        assert_eq!(read_to_string(save_dir.join("Game/snapshots/1/{GameRoot}/save.dat")).unwrap(), "save");
+
+        // Made on another device after pulling
+        directory::Directory::new(remote_dir.clone()).write("Other/snapshots/1/aletheia_manifest.yaml", b"name: Other").unwrap();
+
+        write(save_dir.join("Game/snapshots/1/aletheia_manifest.yaml"), "name: Game\npinned: true").unwrap();
+        create_dir_all(save_dir.join("Game/snapshots/2")).unwrap();
+        write(save_dir.join("Game/snapshots/2/aletheia_manifest.yaml"), "name: Game").unwrap();
+        remove_file(save_dir.join("Game/snapshots/1/{GameRoot}/save.dat")).unwrap();
+        mirror.push().unwrap();
+
+        let remote = mirror.storage.list().unwrap();
+        assert!(!remote.contains_key("Game/snapshots/1/{GameRoot}/save.dat"));
+        assert!(remote.contains_key("Game/snapshots/2/aletheia_manifest.yaml"));
+        assert!(remote.contains_key("Other/snapshots/1/aletheia_manifest.yaml"));
+        assert_eq!(read_to_string(remote_dir.join("Game/snapshots/1/aletheia_manifest.yaml")).unwrap(), "name: Game\npinned: true");
+
+        // Pruned on another device, while the pin is changed locally without pushing
+        directory::Directory::new(remote_dir.clone()).delete("Other/snapshots/1/aletheia_manifest.yaml").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        write(save_dir.join("Game/snapshots/1/aletheia_manifest.yaml"), "name: Game").unwrap();
+        mirror.pull().unwrap();
+        assert!(!save_dir.join("Other/snapshots/1/aletheia_manifest.yaml").exists());
+        assert_eq!(read_to_string(save_dir.join("Game/snapshots/1/aletheia_manifest.yaml")).unwrap(), "name: Game");
+
+        mirror.push().unwrap();
+        assert_eq!(read_to_string(remote_dir.join("Game/snapshots/1/aletheia_manifest.yaml")).unwrap(), "name: Game");
+
+        remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    #[ignore = "Needs rclone"]
+    fn test_rclone() {
+        let root = std::env::temp_dir().join("aletheia_rclone_test");
+        let save_dir = root.join("cache");
+        let _ = remove_dir_all(&root);
+        let backend = StorageBackend::Rclone { remote: format!(":local:{}", root.join("remote").display()) };
+        let storage = open_storage(&backend).unwrap().unwrap();
+
+        assert!(storage.list().unwrap().is_empty());
+        storage.write("Game/snapshots/1/aletheia_manifest.yaml", b"name: Game").unwrap();
+
+        let mut mirror = Mirror { storage, backend, save_dir: save_dir.clone(), remote: HashMap::new(), local: HashMap::new() };
+        mirror.pull().unwrap();
+        assert_eq!(read_to_string(save_dir.join("Game/snapshots/1/aletheia_manifest.yaml")).unwrap(), "name: Game");
+
+        create_dir_all(save_dir.join("Game/snapshots/2")).unwrap();
+        write(save_dir.join("Game/snapshots/2/aletheia_manifest.yaml"), "name: Game").unwrap();
+        remove_dir_all(save_dir.join("Game/snapshots/1")).unwrap();
+        mirror.push().unwrap();
+
+        let remote = mirror.storage.list().unwrap();
+        assert_eq!(remote.keys().collect::<Vec<_>>(), ["Game/snapshots/2/aletheia_manifest.yaml"]);
+        mirror.storage.delete("Game/snapshots/1/aletheia_manifest.yaml").unwrap();
+
+        remove_dir_all(&root).unwrap();
+    }
+
+    /// Runs against the backend described by `ALETHEIA_TEST_STORAGE`, a storage config in JSON such as
+    /// `{"type": "s3", "endpoint": "http://localhost:9000", "region": "us-east-1", "bucket": "aletheia", "path_style": true}`
+    /// for a self-hosted S3 server, or an SFTP or `WebDAV` server running in a container.
+    #[test]
+    #[ignore = "Needs a storage server"]
+    fn test_remote_storage() {
+        let backend: StorageBackend = serde_json::from_str(&std::env::var("ALETHEIA_TEST_STORAGE").unwrap()).unwrap();
+        let storage = open_storage(&backend).unwrap().unwrap();
+        let path = "aletheia-test/Game Name/snapshots/1/{GameRoot}/save 1.dat";
+
+        storage.write(path, b"save data").unwrap();
+        assert_eq!(storage.list().unwrap().get(path), Some(&9));
+        assert_eq!(storage.read(path).unwrap(), b"save data");
+
+        storage.delete(path).unwrap();
+        storage.delete(path).unwrap();
+        assert!(!storage.list().unwrap().contains_key(path));
+    }
+}
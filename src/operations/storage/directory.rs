@@ -0,0 +1,54 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Result, Storage, partial_path, walk};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read, remove_file, rename, write};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+pub struct Directory {
+    root: PathBuf
+}
+
+impl Directory {
+    pub const fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+impl Storage for Directory {
+    fn list(&self) -> Result<HashMap<String, u64>> {
+        let mut files = HashMap::new();
+
+        for path in walk(&self.root)? {
+            let relative = path.strip_prefix(&self.root).unwrap();
+            let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+
+            files.insert(relative, path.metadata()?.len());
+        }
+
+        Ok(files)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(read(self.root.join(path))?)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> Result<()> {
+        let dest = self.root.join(path);
+        let partial = partial_path(&dest);
+
+        create_dir_all(dest.parent().unwrap())?;
+        write(&partial, data)?;
+
+        Ok(rename(partial, dest)?)
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        match remove_file(self.root.join(path)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(())
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Result, Storage};
+use std::collections::HashMap;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Output, Stdio};
+
+/// rclone's exit code when a directory doesn't exist.
+const DIRECTORY_NOT_FOUND: i32 = 3;
+/// rclone's exit code when a file doesn't exist.
+const FILE_NOT_FOUND: i32 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("rclone isn't installed or isn't in PATH")]
+    NotInstalled,
+    #[error("Failed to run rclone: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("rclone {0} failed: {1}")]
+    Failed(&'static str, String),
+    #[error("Malformed rclone output: {0}")]
+    MalformedOutput(#[from] serde_json::Error)
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Entry {
+    path: String,
+    size: u64
+}
+
+/// Shells out to `rclone`, so any remote set up with `rclone config` works, such as `gdrive:aletheia`.
+pub struct Rclone {
+    remote: String
+}
+
+impl Rclone {
+    pub fn new(remote: &str) -> Self {
+        Self { remote: remote.trim_end_matches('/').to_owned() }
+    }
+
+    fn path(&self, path: &str) -> String {
+        // `remote:` is the root of the remote, while `remote:/` is the root of its file system
+        if self.remote.ends_with(':') {
+            format!("{}{path}", self.remote)
+        } else {
+            format!("{}/{path}", self.remote)
+        }
+    }
+}
+
+fn run(command: &'static str, args: &[&str], input: Option<&[u8]>) -> core::result::Result<Output, Error> {
+    let mut child = Command::new("rclone")
+        .arg(command)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::NotInstalled,
+            _ => Error::Io(e)
+        })?;
+
+    if let Some(input) = input {
+        // Dropping stdin closes it, so rclone knows the input ended
+        child.stdin.take().unwrap().write_all(input)?;
+    }
+
+    Ok(child.wait_with_output()?)
+}
+
+fn check(command: &'static str, output: Output) -> core::result::Result<Output, Error> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(Error::Failed(command, String::from_utf8_lossy(&output.stderr).trim().to_owned()))
+    }
+}
+
+impl Storage for Rclone {
+    fn list(&self) -> Result<HashMap<String, u64>> {
+        let output = run("lsjson", &["--recursive", "--files-only", "--no-mimetype", "--no-modtime", &self.remote], None)?;
+
+        // Nothing was backed up yet
+        if output.status.code() == Some(DIRECTORY_NOT_FOUND) {
+            return Ok(HashMap::new());
+        }
+
+        let entries: Vec<Entry> = serde_json::from_slice(&check("lsjson", output)?.stdout).map_err(Error::from)?;
+
+        Ok(entries.into_iter().map(|entry| (entry.path, entry.size)).collect())
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(check("cat", run("cat", &[&self.path(path)], None)?)?.stdout)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> Result<()> {
+        check("rcat", run("rcat", &[&self.path(path)], Some(data))?)?;
+
+        Ok(())
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        let output = run("deletefile", &[&self.path(path)], None)?;
+
+        if !matches!(output.status.code(), Some(DIRECTORY_NOT_FOUND | FILE_NOT_FOUND)) {
+            check("deletefile", output)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Error, Result, Storage, check};
+use hmac::{Hmac, Mac};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::blocking::{Client, Response};
+use reqwest::{Method, StatusCode, Url, header};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+pub const ACCESS_KEY_VAR: &str = "AWS_ACCESS_KEY_ID";
+pub const SECRET_KEY_VAR: &str = "AWS_SECRET_ACCESS_KEY";
+pub const SESSION_TOKEN_VAR: &str = "AWS_SESSION_TOKEN";
+/// Characters that aren't encoded when signing, as defined by Signature Version 4.
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    contents: Vec<Object>,
+    #[serde(default)]
+    is_truncated: bool,
+    next_continuation_token: Option<String>
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Object {
+    key: String,
+    size: u64
+}
+
+pub struct S3 {
+    client: Client,
+    /// URL of the bucket, ending with `/`.
+    bucket_url: Url,
+    region: String,
+    prefix: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>
+}
+
+impl S3 {
+    pub fn new(endpoint: &str, region: &str, bucket: &str, prefix: &str, path_style: bool) -> Result<Self> {
+        let access_key = std::env::var(ACCESS_KEY_VAR).map_err(|_| Error::MissingCredentials(ACCESS_KEY_VAR))?;
+        let secret_key = std::env::var(SECRET_KEY_VAR).map_err(|_| Error::MissingCredentials(SECRET_KEY_VAR))?;
+        let mut bucket_url =
+            Url::parse(endpoint).map_err(|e| Error::MalformedResponse(format!("Invalid endpoint {endpoint}: {e}")))?;
+
+        if path_style {
+            bucket_url.set_path(&format!("{}/{bucket}/", bucket_url.path().trim_end_matches('/')));
+        } else {
+            let host = format!("{bucket}.{}", bucket_url.host_str().unwrap_or_default());
+            bucket_url.set_host(Some(&host)).map_err(|e| Error::MalformedResponse(format!("Invalid bucket {bucket}: {e}")))?;
+            bucket_url.set_path("/");
+        }
+
+        let prefix = prefix.trim_matches('/');
+
+        Ok(Self {
+            client: Client::new(),
+            bucket_url,
+            region: region.to_owned(),
+            prefix: if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{prefix}/")
+            },
+            access_key,
+            secret_key,
+            session_token: std::env::var(SESSION_TOKEN_VAR).ok()
+        })
+    }
+
+    /// Sends a request signed with Signature Version 4.
+    fn send(&self, method: Method, key: &str, query: &[(&str, &str)], body: Vec<u8>) -> Result<Response> {
+        let now = chrono::Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let path = format!("{}{}", self.bucket_url.path(), encode(key, false));
+        let mut query: Vec<(String, String)> = query.iter().map(|(name, value)| (encode(name, true), encode(value, true))).collect();
+        query.sort();
+        let query = query.iter().map(|(name, value)| format!("{name}={value}")).collect::<Vec<_>>().join("&");
+
+        let host = self.bucket_url.host_str().unwrap_or_default();
+        let host = self.bucket_url.port().map_or_else(|| host.to_owned(), |port| format!("{host}:{port}"));
+        let mut headers = vec![("host", host), ("x-amz-content-sha256", payload_hash.clone()), ("x-amz-date", timestamp.clone())];
+
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        let canonical_headers = headers.iter().map(|(name, value)| format!("{name}:{}", value.trim())).collect::<Vec<_>>().join("\n");
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_request = format!("{method}\n{path}\n{query}\n{canonical_headers}\n\n{signed_headers}\n{payload_hash}");
+
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}", hex::encode(Sha256::digest(canonical_request)));
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(hmac(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes()), |key, part| hmac(&key, part.as_bytes()));
+        let signature = hex::encode(hmac(&signing_key, string_to_sign.as_bytes()));
+
+        let mut url = self.bucket_url.clone();
+        url.set_path(&path);
+        url.set_query((!query.is_empty()).then_some(query.as_str()));
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header(header::USER_AGENT, concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            .header(
+                header::AUTHORIZATION,
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    self.access_key
+                )
+            );
+
+        for (name, value) in headers.into_iter().skip(1) {
+            request = request.header(name, value);
+        }
+
+        Ok(request.body(body).send()?)
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Object keys keep their `/`, while query parameters have it encoded.
+fn encode(value: &str, encode_slash: bool) -> String {
+    if encode_slash {
+        utf8_percent_encode(value, UNRESERVED).to_string()
+    } else {
+        value.split('/').map(|segment| utf8_percent_encode(segment, UNRESERVED).to_string()).collect::<Vec<_>>().join("/")
+    }
+}
+
+impl Storage for S3 {
+    fn list(&self) -> Result<HashMap<String, u64>> {
+        let mut files = HashMap::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", self.prefix.as_str())];
+
+            if let Some(token) = &continuation_token {
+                query.push(("continuation-token", token.as_str()));
+            }
+
+            let body = check(self.send(Method::GET, "", &query, vec![])?, "ListObjectsV2")?.text()?;
+            let result: ListBucketResult = quick_xml::de::from_str(&body).map_err(|e| Error::MalformedResponse(e.to_string()))?;
+
+            for object in result.contents {
+                if let Some(path) = object.key.strip_prefix(&self.prefix) {
+                    files.insert(path.to_owned(), object.size);
+                }
+            }
+
+            match result.next_continuation_token {
+                Some(token) if result.is_truncated => continuation_token = Some(token),
+                _ => return Ok(files)
+            }
+        }
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let key = format!("{}{path}", self.prefix);
+
+        Ok(check(self.send(Method::GET, &key, &[], vec![])?, "GET")?.bytes()?.to_vec())
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> Result<()> {
+        let key = format!("{}{path}", self.prefix);
+        check(self.send(Method::PUT, &key, &[], data.to_vec())?, "PUT")?;
+
+        Ok(())
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        let key = format!("{}{path}", self.prefix);
+        let response = self.send(Method::DELETE, &key, &[], vec![])?;
+
+        // S3 itself succeeds when deleting missing objects, but not every compatible server does
+        if response.status() != StatusCode::NOT_FOUND {
+            check(response, "DELETE")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Error, Result, Storage};
+use crate::dirs::home;
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, RenameFlags, Session};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+pub const PASSWORD_VAR: &str = "ALETHEIA_SFTP_PASSWORD";
+/// `LIBSSH2_FX_NO_SUCH_FILE`
+const NO_SUCH_FILE: i32 = 2;
+
+pub struct Sftp {
+    sftp: ssh2::Sftp,
+    root: PathBuf
+}
+
+impl Sftp {
+    pub fn connect(host: &str, port: u16, username: &str, root: &str, private_key: Option<&Path>) -> Result<Self> {
+        let mut session = Session::new()?;
+
+        session.set_tcp_stream(TcpStream::connect((host, port))?);
+        session.handshake()?;
+        verify_host(&session, host, port)?;
+
+        if let Some(private_key) = private_key {
+            session.userauth_pubkey_file(username, None, private_key, None)?;
+        } else if let Ok(password) = std::env::var(PASSWORD_VAR) {
+            session.userauth_password(username, &password)?;
+        } else {
+            session.userauth_agent(username)?;
+        }
+
+        Ok(Self { sftp: session.sftp()?, root: PathBuf::from(root) })
+    }
+
+    fn list_dir(&self, dir: &Path, files: &mut HashMap<String, u64>) -> Result<()> {
+        for (path, stat) in self.sftp.readdir(dir)? {
+            if stat.is_dir() {
+                self.list_dir(&path, files)?;
+            } else if stat.is_file() {
+                let relative = path.strip_prefix(&self.root).unwrap();
+                let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+
+                files.insert(relative, stat.size.unwrap_or_default());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves `partial` over `dest` on servers that can't do it in one step.
+    /// OpenSSH's posix-rename extension does, but ssh2 doesn't expose it, so the old copy is moved aside instead and only removed
+    /// once the new one is in place, so `dest` is never lost.
+    fn replace(&self, partial: &Path, dest: &Path) -> Result<()> {
+        let mut aside = dest.as_os_str().to_owned();
+        // Ends in .partial so it's never synced if it's left behind
+        aside.push(".old.partial");
+        let aside = PathBuf::from(aside);
+
+        let moved_aside = match self.sftp.rename(dest, &aside, None) {
+            Ok(()) => true,
+            Err(e) if is_not_found(&e) => false,
+            Err(_) => {
+                // Left behind by an interrupted write, while dest is still in place
+                self.unlink(&aside)?;
+                self.sftp.rename(dest, &aside, None)?;
+                true
+            }
+        };
+
+        if let Err(e) = self.sftp.rename(partial, dest, None) {
+            if moved_aside {
+                self.sftp.rename(&aside, dest, None)?;
+            }
+
+            return Err(e.into());
+        }
+
+        if moved_aside {
+            self.unlink(&aside)?;
+        }
+
+        Ok(())
+    }
+
+    fn unlink(&self, path: &Path) -> Result<()> {
+        match self.sftp.unlink(path) {
+            Err(e) if !is_not_found(&e) => Err(e.into()),
+            _ => Ok(())
+        }
+    }
+
+    fn create_dir_all(&self, dir: &Path) -> Result<()> {
+        if self.sftp.stat(dir).is_ok() {
+            return Ok(());
+        }
+
+        if let Some(parent) = dir.parent() {
+            self.create_dir_all(parent)?;
+        }
+
+        Ok(self.sftp.mkdir(dir, 0o755)?)
+    }
+}
+
+/// Only hosts already in `~/.ssh/known_hosts` are trusted, the same as connecting with `ssh` after its first prompt.
+fn verify_host(session: &Session, host: &str, port: u16) -> Result<()> {
+    let mut known_hosts = session.known_hosts()?;
+    let (key, _) = session.host_key().ok_or_else(|| Error::UnknownHost(host.to_owned()))?;
+
+    // A missing known_hosts file is the same as the host not being in it
+    let _ = known_hosts.read_file(&home().join(".ssh/known_hosts"), KnownHostFileKind::OpenSSH);
+
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        _ => Err(Error::UnknownHost(host.to_owned()))
+    }
+}
+
+fn is_not_found(e: &ssh2::Error) -> bool {
+    e.code() == ErrorCode::SFTP(NO_SUCH_FILE)
+}
+
+impl Storage for Sftp {
+    fn list(&self) -> Result<HashMap<String, u64>> {
+        let mut files = HashMap::new();
+
+        match self.list_dir(&self.root, &mut files) {
+            Err(Error::Sftp(e)) if is_not_found(&e) => Ok(files),
+            result => result.map(|()| files)
+        }
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let mut data = vec![];
+
+        self.sftp.open(self.root.join(path))?.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> Result<()> {
+        let dest = self.root.join(path);
+        let partial = super::partial_path(&dest);
+
+        self.create_dir_all(dest.parent().unwrap())?;
+
+        self.sftp.create(&partial)?.write_all(data)?;
+
+        // Servers only speaking SFTP version 3 can't rename over an existing file
+        if self.sftp.rename(&partial, &dest, Some(RenameFlags::OVERWRITE | RenameFlags::ATOMIC)).is_err() {
+            self.replace(&partial, &dest)?;
+        }
+
+        Ok(())
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        self.unlink(&self.root.join(path))
+    }
+}
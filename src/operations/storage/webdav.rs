@@ -0,0 +1,212 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Error, Result, Storage, check};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{Method, StatusCode, header};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+pub const PASSWORD_VAR: &str = "ALETHEIA_WEBDAV_PASSWORD";
+/// Characters that don't need to be encoded in a path segment.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+const PROPFIND_BODY: &str =
+    r#"<?xml version="1.0"?><propfind xmlns="DAV:"><prop><resourcetype/><getcontentlength/></prop></propfind>"#;
+
+#[derive(serde::Deserialize)]
+struct MultiStatus {
+    #[serde(default)]
+    response: Vec<PropFindResponse>
+}
+
+#[derive(serde::Deserialize)]
+struct PropFindResponse {
+    href: String,
+    #[serde(default)]
+    propstat: Vec<PropStat>
+}
+
+#[derive(serde::Deserialize)]
+struct PropStat {
+    prop: Prop
+}
+
+#[derive(serde::Deserialize)]
+struct Prop {
+    resourcetype: Option<ResourceType>,
+    getcontentlength: Option<String>
+}
+
+#[derive(serde::Deserialize)]
+struct ResourceType {
+    collection: Option<()>
+}
+
+pub struct WebDav {
+    client: Client,
+    url: String,
+    /// Path of `url` on the server, which hrefs in responses start with.
+    base_path: String,
+    credentials: Option<(String, String)>,
+    /// Collections known to exist, so they aren't created again for every file.
+    collections: RefCell<HashSet<String>>
+}
+
+impl WebDav {
+    pub fn new(url: &str, username: Option<String>) -> Result<Self> {
+        let credentials = match username {
+            Some(username) => Some((username, std::env::var(PASSWORD_VAR).map_err(|_| Error::MissingCredentials(PASSWORD_VAR))?)),
+            None => None
+        };
+        let url = url.trim_end_matches('/').to_owned();
+        let base_path = reqwest::Url::parse(&url)
+            .map_err(|e| Error::MalformedResponse(format!("Invalid URL {url}: {e}")))?
+            .path()
+            .trim_end_matches('/')
+            .to_owned();
+
+        Ok(Self { client: Client::new(), url, base_path, credentials, collections: RefCell::new(HashSet::new()) })
+    }
+
+    fn url(&self, path: &str) -> String {
+        let encoded: Vec<String> = path.split('/').map(|segment| utf8_percent_encode(segment, SEGMENT).to_string()).collect();
+
+        format!("{}/{}", self.url, encoded.join("/"))
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, self.url(path))
+            .header(header::USER_AGENT, concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")));
+
+        match &self.credentials {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request
+        }
+    }
+
+    /// Lists the immediate children of the collection at `dir` along with the size of files, or `None` for collections.
+    fn propfind(&self, dir: &str) -> Result<Vec<(String, Option<u64>)>> {
+        let response = self
+            .request(
+                Method::from_bytes(b"PROPFIND").unwrap(),
+                &if dir.is_empty() {
+                    String::new()
+                } else {
+                    format!("{dir}/")
+                }
+            )
+            .header("Depth", "1")
+            .header(header::CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_BODY)
+            .send()?;
+        let body = check(response, "PROPFIND")?.text()?;
+        let status: MultiStatus = quick_xml::de::from_str(&body).map_err(|e| Error::MalformedResponse(e.to_string()))?;
+        let mut children = vec![];
+
+        for response in status.response {
+            let path = self.relative_path(&response.href);
+
+            if path == dir {
+                continue;
+            }
+
+            let props: Vec<Prop> = response.propstat.into_iter().map(|propstat| propstat.prop).collect();
+
+            if props.iter().any(|prop| prop.resourcetype.as_ref().is_some_and(|kind| kind.collection.is_some())) {
+                children.push((path, None));
+            } else {
+                let size = props.iter().find_map(|prop| prop.getcontentlength.as_ref()?.parse().ok()).unwrap_or_default();
+                children.push((path, Some(size)));
+            }
+        }
+
+        Ok(children)
+    }
+
+    /// Hrefs are either absolute paths or full URLs.
+    fn relative_path(&self, href: &str) -> String {
+        let path = reqwest::Url::parse(href).map_or_else(|_| href.to_owned(), |url| url.path().to_owned());
+        let path = percent_decode_str(&path).decode_utf8_lossy();
+        let base_path = percent_decode_str(&self.base_path).decode_utf8_lossy();
+
+        path.strip_prefix(base_path.as_ref()).unwrap_or(&path).trim_matches('/').to_owned()
+    }
+
+    fn create_collections(&self, path: &str) -> Result<()> {
+        let mut collection = String::new();
+
+        for segment in path.split('/').rev().skip(1).collect::<Vec<_>>().into_iter().rev() {
+            if !collection.is_empty() {
+                collection.push('/');
+            }
+
+            collection.push_str(segment);
+
+            if self.collections.borrow().contains(&collection) {
+                continue;
+            }
+
+            let response = self.request(Method::from_bytes(b"MKCOL").unwrap(), &format!("{collection}/")).send()?;
+
+            // Method Not Allowed means the collection already exists
+            if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                check(response, "MKCOL")?;
+            }
+
+            self.collections.borrow_mut().insert(collection.clone());
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for WebDav {
+    fn list(&self) -> Result<HashMap<String, u64>> {
+        let mut files = HashMap::new();
+        let mut pending = vec![String::new()];
+
+        while let Some(dir) = pending.pop() {
+            match self.propfind(&dir) {
+                Ok(children) => {
+                    for (path, size) in children {
+                        if let Some(size) = size {
+                            files.insert(path, size);
+                        } else {
+                            self.collections.borrow_mut().insert(path.clone());
+                            pending.push(path);
+                        }
+                    }
+                }
+                // Nothing was backed up yet
+                Err(Error::Status(_, StatusCode::NOT_FOUND)) if dir.is_empty() => {}
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(check(self.request(Method::GET, path).send()?, "GET")?.bytes()?.to_vec())
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.create_collections(path)?;
+        check(self.request(Method::PUT, path).body(data.to_vec()).send()?, "PUT")?;
+
+        Ok(())
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        let response = self.request(Method::DELETE, path).send()?;
+
+        if response.status() != StatusCode::NOT_FOUND {
+            check(response, "DELETE")?;
+        }
+
+        Ok(())
+    }
+}
@@ -32,7 +32,21 @@ pub struct Game {
     pub installation_dir: Option<PathBuf>,
     #[cfg(unix)]
     pub prefix: Option<PathBuf>,
-    pub source: String
+    pub source: String,
+    /// Matched against the game database before the name, which can differ between stores and languages.
+    pub store_id: Option<StoreId>
+}
+
+/// A game's ID in the store it was installed from, for the stores scanned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StoreId {
+    Steam(u32),
+    Gog(u64),
+    /// The app name legendary installed the game under.
+    Epic(String),
+    /// The product ID nile installed the game under.
+    Amazon(String),
+    Lutris(String)
 }
 
 pub trait Scanner {
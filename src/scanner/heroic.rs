@@ -1,7 +1,8 @@
 // SPDX-FileCopyrightText: 2025 Spencer
 // SPDX-License-Identifier: AGPL-3.0-only
-use super::{Game, Scanner};
+use super::{Game, Scanner, StoreId};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 
@@ -40,6 +41,34 @@ struct HeroicGOGManifest {
     installed: Vec<HeroicGOGGame>
 }
 
+/// A game installed from Epic through legendary, keyed by its app name in `installed.json`.
+#[derive(Deserialize, Debug)]
+struct HeroicEpicGame {
+    app_name: String,
+    title: String,
+    install_path: PathBuf,
+    #[cfg(unix)]
+    platform: String
+}
+
+/// A game installed from Amazon through nile, which only installs Windows games.
+#[derive(Deserialize, Debug)]
+struct HeroicAmazonGame {
+    id: String,
+    path: PathBuf
+}
+
+#[derive(Deserialize, Debug)]
+struct HeroicAmazonProduct {
+    title: String
+}
+
+#[derive(Deserialize, Debug)]
+struct HeroicAmazonLibraryGame {
+    id: String,
+    product: HeroicAmazonProduct
+}
+
 impl HeroicScanner {
     fn get_game_name(heroic_path: &Path, game: &HeroicGOGGame) -> Option<String> {
         let manifest_path = heroic_path.join("gogdlConfig/heroic_gogdl/manifests").join(&game.app_id);
@@ -61,6 +90,120 @@ impl HeroicScanner {
         #[cfg(any(windows, target_os = "macos"))]
         None
     }
+
+    /// The Wine prefix Heroic runs a Windows game in.
+    #[cfg(unix)]
+    fn get_prefix(heroic_path: &Path, app_id: &str) -> Option<PathBuf> {
+        let game_config = heroic_path.join("GamesConfig").join(format!("{app_id}.json"));
+        let game_config = serde_json::from_reader::<File, serde_json::Value>(File::open(game_config).ok()?).ok()?;
+
+        game_config.get(app_id).and_then(|c| c.get("winePrefix")).and_then(|p| p.as_str()).map(Into::into)
+    }
+
+    fn get_gog_games(heroic_path: &Path, games: &mut Vec<Game>) {
+        let gog_manifest = heroic_path.join("gog_store/installed.json");
+
+        if !gog_manifest.exists() {
+            return;
+        }
+
+        let Ok(gog_manifest) = serde_json::from_reader::<File, HeroicGOGManifest>(File::open(gog_manifest).unwrap()) else {
+            log::error!("Failed to parse GOG manifest.");
+            return;
+        };
+
+        for game in gog_manifest.installed {
+            let Some(game_name) = Self::get_game_name(heroic_path, &game) else {
+                continue;
+            };
+
+            #[cfg(unix)]
+            let prefix = match (game.platform == "windows").then(|| Self::get_prefix(heroic_path, &game.app_id)) {
+                // Windows games without a prefix haven't been set up yet
+                Some(None) => continue,
+                prefix => prefix.flatten()
+            };
+
+            games.push(Game {
+                name: game_name,
+                installation_dir: Some(game.install_path),
+                #[cfg(unix)]
+                prefix,
+                source: "Heroic".into(),
+                store_id: game.app_id.parse().ok().map(StoreId::Gog)
+            });
+        }
+    }
+
+    fn get_epic_games(heroic_path: &Path, games: &mut Vec<Game>) {
+        let epic_manifest = heroic_path.join("legendaryConfig/legendary/installed.json");
+
+        if !epic_manifest.exists() {
+            return;
+        }
+
+        let Ok(epic_manifest) = serde_json::from_reader::<File, HashMap<String, HeroicEpicGame>>(File::open(epic_manifest).unwrap())
+        else {
+            log::error!("Failed to parse Epic manifest.");
+            return;
+        };
+
+        for game in epic_manifest.into_values() {
+            #[cfg(unix)]
+            let prefix = match (game.platform == "Windows").then(|| Self::get_prefix(heroic_path, &game.app_name)) {
+                // Windows games without a prefix haven't been set up yet
+                Some(None) => continue,
+                prefix => prefix.flatten()
+            };
+
+            games.push(Game {
+                name: game.title,
+                installation_dir: Some(game.install_path),
+                #[cfg(unix)]
+                prefix,
+                source: "Heroic".into(),
+                store_id: Some(StoreId::Epic(game.app_name))
+            });
+        }
+    }
+
+    fn get_amazon_games(heroic_path: &Path, games: &mut Vec<Game>) {
+        let amazon_manifest = heroic_path.join("nile_config/nile/installed.json");
+        let amazon_library = heroic_path.join("nile_config/nile/library.json");
+
+        if !amazon_manifest.exists() || !amazon_library.exists() {
+            return;
+        }
+
+        let (Ok(amazon_manifest), Ok(amazon_library)) = (
+            serde_json::from_reader::<File, Vec<HeroicAmazonGame>>(File::open(amazon_manifest).unwrap()),
+            serde_json::from_reader::<File, Vec<HeroicAmazonLibraryGame>>(File::open(amazon_library).unwrap())
+        ) else {
+            log::error!("Failed to parse Amazon manifest.");
+            return;
+        };
+
+        for game in amazon_manifest {
+            // nile only keeps the title in the library
+            let Some(product) = amazon_library.iter().find(|g| g.id == game.id).map(|g| &g.product) else {
+                continue;
+            };
+
+            #[cfg(unix)]
+            let Some(prefix) = Self::get_prefix(heroic_path, &game.id) else {
+                continue;
+            };
+
+            games.push(Game {
+                name: product.title.clone(),
+                installation_dir: Some(game.path),
+                #[cfg(unix)]
+                prefix: Some(prefix),
+                source: "Heroic".into(),
+                store_id: Some(StoreId::Amazon(game.id))
+            });
+        }
+    }
 }
 
 impl Scanner for HeroicScanner {
@@ -87,45 +230,9 @@ impl Scanner for HeroicScanner {
             return games;
         };
 
-        let gog_manifest = heroic_path.join("gog_store/installed.json");
-
-        if !gog_manifest.exists() {
-            return games;
-        }
-
-        let Ok(gog_manifest) = serde_json::from_reader::<File, HeroicGOGManifest>(File::open(gog_manifest).unwrap()) else {
-            log::error!("Failed to parse GOG manifest.");
-            return games;
-        };
-
-        for game in gog_manifest.installed {
-            let Some(game_name) = Self::get_game_name(&heroic_path, &game) else {
-                continue;
-            };
-
-            #[cfg(unix)]
-            let prefix = if game.platform == "windows" {
-                let game_config = heroic_path.join("GamesConfig").join(format!("{}.json", &game.app_id));
-
-                if !game_config.exists() {
-                    continue;
-                }
-
-                let Ok(game_config) = serde_json::from_reader::<File, serde_json::Value>(File::open(game_config).unwrap()) else {
-                    continue;
-                };
-
-                game_config.get(&game.app_id).and_then(|c| c.get("winePrefix")).and_then(|p| p.as_str()).map(Into::into)
-            } else {
-                None
-            };
-
-            #[cfg(unix)]
-            games.push(Game { name: game_name, installation_dir: Some(game.install_path), prefix, source: "Heroic".into() });
-
-            #[cfg(windows)]
-            games.push(Game { name: game_name, installation_dir: Some(game.install_path), source: "Heroic".into() });
-        }
+        Self::get_gog_games(&heroic_path, &mut games);
+        Self::get_epic_games(&heroic_path, &mut games);
+        Self::get_amazon_games(&heroic_path, &mut games);
 
         games
     }
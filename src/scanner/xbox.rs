@@ -64,7 +64,12 @@ impl Scanner for XboxScanner {
                 continue;
             }
 
-            games.push(Game { name: config.shell_visuals.default_display_name, installation_dir: Some(path), source: "Xbox".into() });
+            games.push(Game {
+                name: config.shell_visuals.default_display_name,
+                installation_dir: Some(path),
+                source: "Xbox".into(),
+                store_id: None
+            });
         }
 
         games
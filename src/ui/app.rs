@@ -3,7 +3,7 @@
 
 slint::include_modules!();
 
-use super::handlers::{games, settings};
+use super::handlers::{conflicts, games, local_changes, settings};
 use crate::config::Config as AletheiaConfig;
 use std::cell::RefCell;
 use std::process::Command;
@@ -55,7 +55,12 @@ pub fn run(config: &AletheiaConfig) {
     slint::set_xdg_app_id("moe.spencer.Aletheia").unwrap();
 
     setup_app_handlers(&app);
-    games::setup(&app_weak, &cfg);
+    // Games restored by the last restore, which the notification's undo button puts back
+    let last_restored = Rc::default();
+
+    conflicts::setup(&app_weak, &cfg);
+    games::setup(&app_weak, &cfg, &last_restored);
+    local_changes::setup(&app_weak, &cfg, &last_restored);
     settings::setup(&app_weak, &cfg);
 
     app.run().unwrap();
@@ -1,5 +1,7 @@
 // SPDX-FileCopyrightText: 2025 Spencer
 // SPDX-License-Identifier: AGPL-3.0-only
 
+pub mod conflicts;
 pub mod games;
+pub mod local_changes;
 pub mod settings;
@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::config::Config as AletheiaConfig;
+use crate::gamedb;
+use crate::operations::{Conflict, Resolution, load_key, pull_storage, resolve_conflict};
+use crate::ui::app::{App, ConflictLogic, GameLogic, NotificationLogic, UiConflict};
+use slint::{ComponentHandle, Model, ModelRc, VecModel};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
+    let app = app.upgrade().unwrap();
+
+    app.global::<ConflictLogic>().on_resolve({
+        let app_weak = app.as_weak().unwrap();
+        let cfg = Rc::clone(config);
+
+        move |ui_conflict, choice| {
+            let cfg = cfg.as_ref().borrow();
+            let conflict_logic = app_weak.global::<ConflictLogic>();
+            let notification_logic = app_weak.global::<NotificationLogic>();
+            let remaining: Vec<UiConflict> = conflict_logic.get_conflicts().iter().filter(|c| *c != ui_conflict).collect();
+
+            conflict_logic.set_conflicts(ModelRc::new(VecModel::from(remaining)));
+
+            let conflict = Conflict {
+                game: ui_conflict.game.to_string(),
+                snapshot: ui_conflict.snapshot.to_string(),
+                hostname: Some(ui_conflict.hostname.to_string()).filter(|hostname| !hostname.is_empty())
+            };
+            let installed_games = gamedb::get_installed_games();
+            let Some(game) = installed_games.iter().find(|g| g.name == conflict.game) else {
+                return;
+            };
+
+            let mut mirror = match pull_storage(&cfg) {
+                Ok(mirror) => mirror,
+                Err(e) => {
+                    log::error!("Failed to download backups: {e}");
+                    notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                    return;
+                }
+            };
+
+            let key = match load_key(&cfg, None) {
+                Ok(key) => key,
+                Err(e) => {
+                    log::error!("Failed to unlock backups: {e}");
+                    notification_logic.invoke_show_error("BACKUPS_LOCKED".into());
+                    return;
+                }
+            };
+
+            let resolution = Resolution::parse(&choice).unwrap();
+
+            if let Err(e) = resolve_conflict(&conflict, game, &gamedb::parse()[&game.name], &cfg, key.as_ref(), resolution) {
+                log::error!("Failed to resolve {}'s conflict: {e}", conflict.game);
+                notification_logic.invoke_show_error(format!("Failed to resolve {}'s conflict", conflict.game).into());
+                return;
+            }
+
+            log::info!("Resolved {}'s conflict.", conflict.game);
+
+            if let Some(mirror) = &mut mirror
+                && let Err(e) = mirror.push()
+            {
+                log::error!("Failed to upload backups: {e}");
+                notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                return;
+            }
+
+            app_weak.global::<GameLogic>().invoke_refresh_games();
+            notification_logic.invoke_show_success(format!("Resolved {}'s conflict", conflict.game).into());
+        }
+    });
+}
+
+/// Queues conflicts found while backing up or restoring, which are then shown one at a time.
+pub fn show(app: &App, conflicts: Vec<Conflict>) {
+    let conflict_logic = app.global::<ConflictLogic>();
+    let mut queued: Vec<UiConflict> = conflict_logic.get_conflicts().iter().collect();
+
+    for conflict in conflicts {
+        let ui_conflict = UiConflict {
+            game: conflict.game.into(),
+            hostname: conflict.hostname.unwrap_or_default().into(),
+            snapshot: conflict.snapshot.into()
+        };
+
+        if !queued.contains(&ui_conflict) {
+            queued.push(ui_conflict);
+        }
+    }
+
+    conflict_logic.set_conflicts(ModelRc::new(VecModel::from(queued)));
+}
@@ -3,27 +3,48 @@
 
 use crate::config::Config as AletheiaConfig;
 use crate::gamedb;
-use crate::operations::{backup_game, restore_game};
-use crate::ui::app::{App, GameLogic, GamesScreenLogic, NotificationLogic, UiGame};
+use crate::operations::{
+    BackupError, BackupOptions, Key, RestoreError, RestoreOptions, Snapshot, SnapshotSelector, backup_game, list_snapshots, load_key,
+    pull_storage, restore_game, select_snapshot, undo_restore
+};
+use crate::ui::app::{App, DropdownOption, GameLogic, GamesScreenLogic, NotificationLogic, UiGame, UiGameFile};
+use crate::ui::handlers::{conflicts, local_changes};
 use crate::utils;
-use slint::{ComponentHandle, Model, ModelRc, VecModel};
+use chrono::{DateTime, Local};
+use glob::Pattern;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
 use std::cell::RefCell;
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::rc::Rc;
 
 #[expect(clippy::too_many_lines, reason = "This is as simple as it's going to get")]
-pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
+pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>, last_restored: &Rc<RefCell<Vec<(String, String)>>>) {
     let app = app.upgrade().unwrap();
     let game_logic = app.global::<GameLogic>();
     let games_screen_logic = app.global::<GamesScreenLogic>();
     let save_dir = config.borrow().save_dir.clone();
+    // Each game's snapshots as of the last refresh, to list the files of the one picked
+    let game_snapshots: Rc<RefCell<HashMap<String, Vec<Snapshot>>>> = Rc::default();
 
     game_logic.on_refresh_games({
         let app_weak = app.as_weak().unwrap();
+        let cfg = Rc::clone(config);
+        let cached_snapshots = Rc::clone(&game_snapshots);
 
         move || {
+            // Falls back to the backups downloaded last time
+            if let Err(e) = pull_storage(&cfg.borrow()) {
+                log::error!("Failed to download backups: {e}");
+            }
+
+            let key = load_key(&cfg.borrow(), None).unwrap_or_else(|e| {
+                log::error!("Failed to unlock backups: {e}");
+                None
+            });
             let games_screen_logic = app_weak.global::<GamesScreenLogic>();
             let selected_games = games_screen_logic.get_selected_games();
+            let previous_games = app_weak.global::<GameLogic>().get_games();
             let select_all = selected_games.row_count() == 0;
 
             let mut games = gamedb::get_installed_games();
@@ -33,17 +54,31 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
                 .into_iter()
                 .map(|g| {
                     let backup_path = save_dir.join(utils::sanitize_game_name(&g.name).as_ref());
-                    let selected = select_all || selected_games.iter().any(|selected| selected.name.as_str() == g.name);
+                    let selected = select_all
+                        || selected_games
+                            .iter()
+                            .any(|selected| selected.name.as_str() == g.name && selected.source.as_str() == g.source);
+                    let game_snapshots = read_game_snapshots(&backup_path, key.as_ref());
+                    let snapshots = snapshot_options(&game_snapshots);
+                    let snapshot = previous_games
+                        .iter()
+                        .find(|previous| previous.name.as_str() == g.name)
+                        .map(|previous| previous.snapshot)
+                        .filter(|id| snapshots.iter().any(|option| option.value == *id))
+                        .unwrap_or_default();
+                    let files = file_options(&game_snapshots, &snapshot);
+                    let backup_size = backup_size(&game_snapshots);
+
+                    cached_snapshots.borrow_mut().insert(g.name.clone(), game_snapshots);
 
                     UiGame {
                         name: g.name.into(),
-                        backup_size: if backup_path.exists() {
-                            format_size(crate::dirs::get_size(&backup_path)).into()
-                        } else {
-                            "0B".into()
-                        },
+                        backup_size: backup_size.into(),
                         source: g.source.into(),
-                        selected
+                        selected,
+                        snapshots: ModelRc::new(VecModel::from(snapshots)),
+                        snapshot,
+                        files: ModelRc::new(VecModel::from(files))
                     }
                 })
                 .collect();
@@ -72,7 +107,7 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
                 .iter()
                 .filter(|g| query.is_empty() || g.name.to_lowercase().contains(&query_lower))
                 .map(|mut g| {
-                    g.selected = selected_games.iter().any(|selected| selected.name == g.name);
+                    g.selected = selected_games.iter().any(|selected| same_install(&selected, &g));
                     g
                 })
                 .collect();
@@ -99,7 +134,7 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
                 .collect();
             let mut selected_games: Vec<UiGame> = games_screen_logic.get_selected_games().iter().collect();
 
-            selected_games.retain(|g| !updated_games.iter().any(|updated| updated.name == g.name));
+            selected_games.retain(|g| !updated_games.iter().any(|updated| same_install(updated, g)));
             if enabled {
                 selected_games.extend(updated_games.iter().cloned());
             }
@@ -119,7 +154,7 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
             let selected_games_model = games_screen_logic.get_selected_games();
             let mut selected_games: Vec<UiGame> = selected_games_model.iter().collect();
 
-            if let Some(index) = selected_games.iter().position(|g| g.name == game.name) {
+            if let Some(index) = selected_games.iter().position(|g| same_install(g, &game)) {
                 selected_games.remove(index);
             } else {
                 selected_games.push(game);
@@ -132,9 +167,42 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
         }
     });
 
+    games_screen_logic.on_select_snapshot({
+        let app_weak = app.as_weak().unwrap();
+
+        move |game, snapshot| {
+            let games_screen_logic = app_weak.global::<GamesScreenLogic>();
+            let files = game_snapshots.borrow().get(game.name.as_str()).map_or_else(Vec::new, |s| file_options(s, &snapshot));
+            let files = ModelRc::new(VecModel::from(files));
+            let models = [
+                app_weak.global::<GameLogic>().get_games(),
+                games_screen_logic.get_filtered_games(),
+                games_screen_logic.get_selected_games()
+            ];
+
+            for model in models {
+                for (index, mut g) in model.iter().enumerate() {
+                    if g.name == game.name {
+                        g.snapshot = snapshot.clone();
+                        g.files = files.clone();
+                        model.set_row_data(index, g);
+                    }
+                }
+            }
+        }
+    });
+
+    // Every copy of a game shares its file model, so updating it once is enough
+    games_screen_logic.on_select_file(|game, path, selected| {
+        if let Some(index) = game.files.iter().position(|file| file.path == path) {
+            game.files.set_row_data(index, UiGameFile { path, selected });
+        }
+    });
+
     games_screen_logic.on_perform_operation({
         let app_weak = app.as_weak().unwrap();
         let cfg = Rc::clone(config);
+        let last_restored = Rc::clone(last_restored);
 
         move |action| {
             let cfg = cfg.as_ref().borrow();
@@ -147,20 +215,51 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
                 return;
             }
 
+            let mut mirror = match pull_storage(&cfg) {
+                Ok(mirror) => mirror,
+                Err(e) => {
+                    log::error!("Failed to download backups: {e}");
+                    notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                    return;
+                }
+            };
+
+            let key = match load_key(&cfg, None) {
+                Ok(key) => key,
+                Err(e) => {
+                    log::error!("Failed to unlock backups: {e}");
+                    notification_logic.invoke_show_error("BACKUPS_LOCKED".into());
+                    return;
+                }
+            };
+
             if action == "backup" {
                 let game_db = gamedb::parse();
                 let mut backed_up = 0;
+                let mut conflicts = vec![];
 
                 for ui_game in selected_games.iter() {
-                    let game = installed_games.iter().find(|g| *g.name == *ui_game.name).unwrap();
-                    if let Err(e) = backup_game(game, &cfg, &game_db[&game.name]) {
-                        log::error!("Failed to backup {}.\n{e}", &game.name);
-                    } else {
-                        log::info!("Successfully backed up {}.", &game.name);
-                        backed_up += 1;
+                    let game = installed_games.iter().find(|g| *g.name == *ui_game.name && *g.source == *ui_game.source).unwrap();
+                    match backup_game(game, &cfg, &game_db[&game.name], key.as_ref(), BackupOptions::default()) {
+                        Ok(_) => {
+                            log::info!("Successfully backed up {}.", &game.name);
+                            backed_up += 1;
+                        }
+                        Err(BackupError::Conflict(conflict)) => conflicts.push(conflict),
+                        Err(e) => log::error!("Failed to backup {}.\n{e}", &game.name)
                     }
                 }
 
+                conflicts::show(&app_weak, conflicts);
+
+                if let Some(mirror) = &mut mirror
+                    && let Err(e) = mirror.push()
+                {
+                    log::error!("Failed to upload backups: {e}");
+                    notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                    return;
+                }
+
                 app_weak.global::<GameLogic>().invoke_refresh_games();
                 notification_logic.invoke_show_success(format!("Backed up {backed_up} games").into());
             } else {
@@ -169,7 +268,11 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
                     return;
                 }
 
-                let mut restored = 0;
+                let game_db = gamedb::parse();
+                let mut restored = vec![];
+                let mut conflicts = vec![];
+                let mut local_changes = vec![];
+
                 for ui_game in selected_games.iter() {
                     let game_name = &ui_game.name;
                     let game_dir = cfg.save_dir.join(utils::sanitize_game_name(game_name).as_ref());
@@ -180,51 +283,198 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
                         continue;
                     }
 
-                    let manifest_path = game_dir.join("aletheia_manifest.yaml");
-
-                    if !manifest_path.exists() {
-                        log::error!("{game_name} is missing a manifest file.");
-                        notification_logic.invoke_show_error(format!("No manifest found for {game_name}").into());
-                        continue;
-                    }
+                    let selector = if ui_game.snapshot.is_empty() {
+                        SnapshotSelector::Latest
+                    } else {
+                        SnapshotSelector::Id(ui_game.snapshot.to_string())
+                    };
 
-                    let Ok(manifest) = serde_yaml::from_reader::<File, gamedb::GameInfo>(File::open(manifest_path).unwrap()) else {
-                        log::error!("Failed to parse {game_name}'s manifest.");
-                        notification_logic.invoke_show_error(format!("{game_name}'s manifest is corrupted").into());
+                    let snapshot = match list_snapshots(&game_dir, key.as_ref()).map(|snapshots| select_snapshot(snapshots, &selector))
+                    {
+                        Ok(Some(snapshot)) => snapshot,
+                        Ok(None) => {
+                            log::warn!("Attempted to restore {game_name} without a matching snapshot.");
+                            notification_logic.invoke_show_warning(format!("No backups found for {game_name}").into());
+                            continue;
+                        }
+                        Err(e) => {
+                            log::error!("Failed to read {game_name}'s snapshots: {e}");
+                            notification_logic.invoke_show_error(format!("{game_name}'s backups are corrupted").into());
+                            continue;
+                        }
+                    };
+                    let manifest = &snapshot.manifest;
+                    let Some(files) = selected_files(&ui_game) else {
+                        notification_logic.invoke_show_warning(format!("No files selected for {game_name}").into());
                         continue;
                     };
+                    let options = RestoreOptions {
+                        files: &files,
+                        source: Some(ui_game.source.as_str()),
+                        entry: game_db.get(game_name.as_str()),
+                        ..RestoreOptions::default()
+                    };
 
-                    if let Err(e) = restore_game(&game_dir, &manifest, &installed_games, &cfg) {
-                        log::error!("Failed to restore {}: {e}", manifest.name);
-                    } else {
-                        log::info!("Successfully restored {game_name}");
-                        restored += 1;
+                    match restore_game(&snapshot, &installed_games, &cfg, key.as_ref(), options) {
+                        Ok(_) => {
+                            log::info!("Successfully restored {game_name}");
+                            restored.push((game_name.to_string(), ui_game.source.to_string()));
+                        }
+                        Err(RestoreError::Conflict(conflict)) => conflicts.push(conflict),
+                        Err(RestoreError::LocalChanges(changes)) => local_changes.push(changes),
+                        Err(e) => log::error!("Failed to restore {}: {e}", manifest.name)
                     }
                 }
 
-                if restored > 0 {
-                    notification_logic.invoke_show_success(format!("Restored {restored} games").into());
+                // Restoring records what this device synced with, which other devices compare against
+                if let Some(mirror) = &mut mirror
+                    && let Err(e) = mirror.push()
+                {
+                    log::error!("Failed to upload backups: {e}");
+                    notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                }
+
+                conflicts::show(&app_weak, conflicts);
+                local_changes::show(&app_weak, local_changes);
+
+                if !restored.is_empty() {
+                    notification_logic
+                        .invoke_show_success_with_action(format!("Restored {} games", restored.len()).into(), "UNDO_RESTORE".into());
+                    *last_restored.borrow_mut() = restored;
+                }
+            }
+        }
+    });
+
+    app.global::<NotificationLogic>().on_action_triggered({
+        let app_weak = app.as_weak().unwrap();
+        let cfg = Rc::clone(config);
+        let last_restored = Rc::clone(last_restored);
+
+        move |action| {
+            if action != "UNDO_RESTORE" {
+                return;
+            }
+
+            let cfg = cfg.as_ref().borrow();
+            let notification_logic = app_weak.global::<NotificationLogic>();
+            let installed_games = gamedb::get_installed_games();
+
+            let mut mirror = match pull_storage(&cfg) {
+                Ok(mirror) => mirror,
+                Err(e) => {
+                    log::error!("Failed to download backups: {e}");
+                    notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                    return;
+                }
+            };
+
+            let key = match load_key(&cfg, None) {
+                Ok(key) => key,
+                Err(e) => {
+                    log::error!("Failed to unlock backups: {e}");
+                    notification_logic.invoke_show_error("BACKUPS_LOCKED".into());
+                    return;
+                }
+            };
+
+            let mut undone = 0;
+
+            for (game_name, source) in last_restored.take() {
+                match undo_restore(&game_name, &installed_games, Some(&source), &cfg, key.as_ref()) {
+                    Ok(true) => {
+                        log::info!("Undid the last restore of {game_name}.");
+                        undone += 1;
+                    }
+                    Ok(false) => log::warn!("{game_name} hasn't been restored on this device."),
+                    Err(e) => log::error!("Failed to undo the last restore of {game_name}: {e}")
                 }
             }
+
+            if let Some(mirror) = &mut mirror
+                && let Err(e) = mirror.push()
+            {
+                log::error!("Failed to upload backups: {e}");
+                notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                return;
+            }
+
+            notification_logic.invoke_show_success(format!("Undid restoring {undone} games").into());
         }
     });
 
     game_logic.invoke_refresh_games();
 }
 
-#[expect(clippy::cast_precision_loss, reason = "Only used for UI")]
-fn format_size(size: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = 1048576;
-    const GB: u64 = 1073741824;
-
-    if size < KB {
-        format!("{size}B")
-    } else if size < MB {
-        format!("{:.1}KB", size as f64 / KB as f64)
-    } else if size < GB {
-        format!("{:.1}MB", size as f64 / MB as f64)
+fn read_game_snapshots(game_dir: &Path, key: Option<&Key>) -> Vec<Snapshot> {
+    if !game_dir.exists() {
+        return vec![];
+    }
+
+    list_snapshots(game_dir, key).unwrap_or_default()
+}
+
+/// The files of the snapshot with `id`, or of the latest one when empty, all selected.
+fn file_options(snapshots: &[Snapshot], id: &str) -> Vec<UiGameFile> {
+    let snapshot = if id.is_empty() {
+        snapshots.iter().rev().find(|snapshot| !snapshot.manifest.conflict)
+    } else {
+        snapshots.iter().find(|snapshot| snapshot.id == id)
+    };
+
+    snapshot
+        .map(|snapshot| &snapshot.manifest.files)
+        .into_iter()
+        .flatten()
+        .map(|file| UiGameFile { path: file.path.as_str().into(), selected: true })
+        .collect()
+}
+
+/// Whether both are the same install, as a game can be installed from more than one launcher.
+fn same_install(a: &UiGame, b: &UiGame) -> bool {
+    a.name == b.name && a.source == b.source
+}
+
+/// Patterns matching the files picked to restore, which are empty when every file is picked, or `None` when none are.
+pub fn selected_files(game: &UiGame) -> Option<Vec<Pattern>> {
+    if game.files.iter().all(|file| file.selected) {
+        return Some(vec![]);
+    }
+
+    let files: Vec<Pattern> =
+        game.files.iter().filter(|file| file.selected).map(|file| Pattern::new(&Pattern::escape(&file.path)).unwrap()).collect();
+
+    (!files.is_empty()).then_some(files)
+}
+
+fn snapshot_options(snapshots: &[Snapshot]) -> Vec<DropdownOption> {
+    snapshots
+        .iter()
+        .rev()
+        .map(|snapshot| DropdownOption {
+            label: DateTime::<Local>::from(snapshot.created()).format("%Y-%m-%d %H:%M").to_string().into(),
+            value: SharedString::from(&snapshot.id)
+        })
+        .collect()
+}
+
+/// Formats the space taken by every stored file of a game, followed by their original size when compressed.
+/// Files shared between snapshots are only counted once.
+fn backup_size(snapshots: &[Snapshot]) -> String {
+    let mut seen = HashSet::new();
+    let mut stored = 0;
+    let mut original = 0;
+
+    for file in snapshots.iter().flat_map(|snapshot| &snapshot.manifest.files) {
+        if seen.insert((&file.hash, file.compression)) {
+            stored += file.stored_size.unwrap_or(file.size);
+            original += file.size;
+        }
+    }
+
+    if stored == original {
+        utils::format_size(stored)
     } else {
-        format!("{:.2}GB", size as f64 / GB as f64)
+        format!("{} / {}", utils::format_size(stored), utils::format_size(original))
     }
 }
@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: 2025 Spencer
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::config::Config as AletheiaConfig;
+use crate::gamedb;
+use crate::operations::{
+    LocalChanges, RestoreOptions, SnapshotSelector, list_snapshots, load_key, pull_storage, restore_game, select_snapshot
+};
+use crate::ui::app::{App, GameLogic, LocalChangesLogic, NotificationLogic, UiLocalChange, UiLocalChanges};
+use crate::ui::handlers::games;
+use crate::utils;
+use slint::{ComponentHandle, Model, ModelRc, VecModel};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>, last_restored: &Rc<RefCell<Vec<(String, String)>>>) {
+    let app = app.upgrade().unwrap();
+    let local_changes_logic = app.global::<LocalChangesLogic>();
+
+    local_changes_logic.on_keep({
+        let app_weak = app.as_weak().unwrap();
+
+        move |ui_changes| {
+            dequeue(&app_weak, &ui_changes);
+            log::info!("Skipped restoring {} to keep its local saves.", ui_changes.game);
+        }
+    });
+
+    local_changes_logic.on_replace({
+        let app_weak = app.as_weak().unwrap();
+        let cfg = Rc::clone(config);
+        let last_restored = Rc::clone(last_restored);
+
+        move |ui_changes| {
+            let cfg = cfg.as_ref().borrow();
+            let notification_logic = app_weak.global::<NotificationLogic>();
+            let game_name = ui_changes.game.to_string();
+
+            dequeue(&app_weak, &ui_changes);
+
+            let mut mirror = match pull_storage(&cfg) {
+                Ok(mirror) => mirror,
+                Err(e) => {
+                    log::error!("Failed to download backups: {e}");
+                    notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                    return;
+                }
+            };
+
+            let key = match load_key(&cfg, None) {
+                Ok(key) => key,
+                Err(e) => {
+                    log::error!("Failed to unlock backups: {e}");
+                    notification_logic.invoke_show_error("BACKUPS_LOCKED".into());
+                    return;
+                }
+            };
+
+            let game_dir = cfg.save_dir.join(utils::sanitize_game_name(&game_name).as_ref());
+            let selector = SnapshotSelector::Id(ui_changes.snapshot.to_string());
+            let Some(snapshot) =
+                list_snapshots(&game_dir, key.as_ref()).ok().and_then(|snapshots| select_snapshot(snapshots, &selector))
+            else {
+                log::error!("Snapshot {} of {game_name} no longer exists.", ui_changes.snapshot);
+                notification_logic.invoke_show_error(format!("Failed to restore {game_name}").into());
+                return;
+            };
+
+            // Restores the same files as the restore being confirmed
+            let ui_game =
+                app_weak.global::<GameLogic>().get_games().iter().find(|g| g.name == ui_changes.game && g.source == ui_changes.source);
+            let files = ui_game.as_ref().and_then(games::selected_files).unwrap_or_default();
+            let game_db = gamedb::parse();
+            let options = RestoreOptions {
+                force: true,
+                files: &files,
+                source: Some(ui_changes.source.as_str()),
+                entry: game_db.get(&game_name),
+                ..RestoreOptions::default()
+            };
+
+            if let Err(e) = restore_game(&snapshot, &gamedb::get_installed_games(), &cfg, key.as_ref(), options) {
+                log::error!("Failed to restore {game_name}: {e}");
+                notification_logic.invoke_show_error(format!("Failed to restore {game_name}").into());
+                return;
+            }
+
+            log::info!("Successfully restored {game_name}");
+
+            if let Some(mirror) = &mut mirror
+                && let Err(e) = mirror.push()
+            {
+                log::error!("Failed to upload backups: {e}");
+                notification_logic.invoke_show_error("STORAGE_SYNC_FAILED".into());
+                return;
+            }
+
+            app_weak.global::<GameLogic>().invoke_refresh_games();
+            notification_logic.invoke_show_success_with_action(format!("Restored {game_name}").into(), "UNDO_RESTORE".into());
+            *last_restored.borrow_mut() = vec![(game_name, ui_changes.source.to_string())];
+        }
+    });
+}
+
+/// Queues games whose restore would replace local saves, which are then confirmed one at a time.
+pub fn show(app: &App, changes: Vec<LocalChanges>) {
+    let local_changes_logic = app.global::<LocalChangesLogic>();
+    let mut queued: Vec<UiLocalChanges> = local_changes_logic
+        .get_pending()
+        .iter()
+        .filter(|c| !changes.iter().any(|n| c.game == n.game && c.source == n.source))
+        .collect();
+
+    for game_changes in changes {
+        let files: Vec<UiLocalChange> = game_changes
+            .files
+            .into_iter()
+            .map(|file| UiLocalChange { path: file.path.to_string_lossy().to_string().into(), newer: file.newer })
+            .collect();
+
+        queued.push(UiLocalChanges {
+            game: game_changes.game.into(),
+            source: game_changes.source.into(),
+            snapshot: game_changes.snapshot.into(),
+            files: ModelRc::new(VecModel::from(files))
+        });
+    }
+
+    local_changes_logic.set_pending(ModelRc::new(VecModel::from(queued)));
+}
+
+fn dequeue(app: &App, ui_changes: &UiLocalChanges) {
+    let local_changes_logic = app.global::<LocalChangesLogic>();
+    let remaining: Vec<UiLocalChanges> =
+        local_changes_logic.get_pending().iter().filter(|c| c.game != ui_changes.game || c.source != ui_changes.source).collect();
+
+    local_changes_logic.set_pending(ModelRc::new(VecModel::from(remaining)));
+}
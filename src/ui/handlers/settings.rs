@@ -41,19 +41,22 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
             let notification_logic = app_weak.global::<NotificationLogic>();
             let settings_logic = app_weak.global::<SettingsScreenLogic>();
 
-            let new_config = AletheiaConfig {
+            let mut new_config = AletheiaConfig {
                 custom_databases: ui_cfg.custom_databases.iter().map(Into::into).collect(),
-                save_dir: (&ui_cfg.save_dir).into(),
                 steam_account_id: (!ui_cfg.steam_account_id.is_empty()).then(|| (&ui_cfg.steam_account_id).into()),
                 #[cfg(feature = "updater")]
-                check_for_updates: ui_cfg.check_for_updates
+                check_for_updates: ui_cfg.check_for_updates,
+                ..cfg.borrow().clone()
             };
 
+            new_config.set_display_save_dir(&ui_cfg.save_dir);
+
             settings_logic.set_previous_save_dir(ui_cfg.save_dir.clone());
             settings_logic.set_previous_steam_account_id(ui_cfg.steam_account_id.clone());
             settings_logic.set_previous_check_for_updates(ui_cfg.check_for_updates);
 
             AletheiaConfig::save(&new_config);
+            new_config.resolve_save_dir();
             *cfg.borrow_mut() = new_config;
 
             notification_logic.invoke_show_success("SAVED_SETTINGS".into());
@@ -113,7 +116,7 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
 
     settings_screen_logic.set_config(Config {
         custom_databases: ModelRc::new(config_ref.custom_databases.iter().map(Into::into).collect::<VecModel<_>>()),
-        save_dir: config_ref.save_dir.to_string_lossy().as_ref().into(),
+        save_dir: config_ref.display_save_dir().into(),
         steam_account_id: steam_account_id_str.into(),
         #[cfg(feature = "updater")]
         check_for_updates: config_ref.check_for_updates,
@@ -121,7 +124,7 @@ pub fn setup(app: &slint::Weak<App>, config: &Rc<RefCell<AletheiaConfig>>) {
         check_for_updates: false
     });
 
-    settings_screen_logic.set_previous_save_dir(config_ref.save_dir.to_string_lossy().as_ref().into());
+    settings_screen_logic.set_previous_save_dir(config_ref.display_save_dir().into());
     settings_screen_logic.set_previous_steam_account_id(steam_account_id_str.into());
 
     #[cfg(feature = "updater")]
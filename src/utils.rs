@@ -8,3 +8,20 @@ pub fn sanitize_game_name(name: &str) -> Cow<'_, str> {
         Cow::Borrowed(name)
     }
 }
+
+#[expect(clippy::cast_precision_loss, reason = "Only used for display")]
+pub fn format_size(size: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1048576;
+    const GB: u64 = 1073741824;
+
+    if size < KB {
+        format!("{size}B")
+    } else if size < MB {
+        format!("{:.1}KB", size as f64 / KB as f64)
+    } else if size < GB {
+        format!("{:.1}MB", size as f64 / MB as f64)
+    } else {
+        format!("{:.2}GB", size as f64 / GB as f64)
+    }
+}